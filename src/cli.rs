@@ -0,0 +1,551 @@
+use std::path::PathBuf;
+
+use crate::errors::{JCloneError, JCloneResult};
+use crate::workspace::Filter;
+
+pub const USAGE: &str = "\
+Usage:
+  jclone [--dry-run] <repository> Clone a repository into its organised location
+  jclone list [--host H] [--json|--paths] [PATTERN]
+                                  List cloned repositories, optionally by host and path pattern
+  jclone status [--host H] [--json] [PATTERN]
+                                  Report uncommitted, unpushed and stashed work in cloned repositories
+  jclone sync [--pull] [--jobs N] [--host H] [PATTERN]
+                                  Fetch, or fast-forward with --pull, cloned repositories
+  jclone exec [--jobs N] [--host H] [--match GLOB] -- <command>...
+                                  Run a command in each cloned repository
+  jclone reorganise [--yes] [DIR...]
+                                  Move checkouts to where the current configuration would clone them
+  jclone adopt [--recursive] [--symlink] <dir>...
+                                  Move repositories cloned without jclone to where it would clone them
+  jclone rm [--force] <repository> Remove a checkout if it has no unpushed work
+  jclone prune --older-than AGE [--archive DIR] [--yes] [--host H] [PATTERN]
+                                  Remove or archive repositories unused for AGE, e.g. 90d or 12w
+  jclone path <query>             Print the directory of the best matching repository
+  jclone index rebuild            Rebuild the index of cloned repositories from disk
+  jclone config check             Parse and validate all configuration files
+  jclone config show [--host H]   Print the effective configuration and where each value comes from
+  jclone config init [--local]    Write a starter configuration file
+  jclone config schema            Print a JSON Schema for configuration files
+  jclone config migrate [--write] Upgrade configuration files to the latest format
+  jclone --help                   Print this message";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Clone {
+        repo_str: String,
+        dry_run: bool,
+    },
+    Config(ConfigCommand),
+    List {
+        filter: Filter,
+        format: ListFormat,
+    },
+    Status {
+        filter: Filter,
+        json: bool,
+    },
+    Sync {
+        filter: Filter,
+        pull: bool,
+        jobs: Option<usize>,
+    },
+    Exec {
+        filter: Filter,
+        jobs: usize,
+        command: Vec<String>,
+    },
+    Reorganise {
+        yes: bool,
+        dirs: Vec<PathBuf>,
+    },
+    Adopt {
+        dirs: Vec<PathBuf>,
+        recursive: bool,
+        symlink: bool,
+    },
+    Rm {
+        query: String,
+        force: bool,
+    },
+    Prune {
+        filter: Filter,
+        /// In seconds.
+        older_than: u64,
+        archive: Option<PathBuf>,
+        yes: bool,
+    },
+    Path {
+        query: String,
+    },
+    Index(IndexCommand),
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IndexCommand {
+    Rebuild,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ListFormat {
+    Table,
+    Paths,
+    Json,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigCommand {
+    Check,
+    Show { host: Option<String> },
+    Init { local: bool },
+    Schema,
+    Migrate { write: bool },
+}
+
+fn unexpected(arg: String) -> JCloneError {
+    JCloneError::Usage(format!("unexpected argument: {arg}"))
+}
+
+fn option_value(args: &mut impl Iterator<Item = String>, option: &str) -> JCloneResult<String> {
+    args.next()
+        .ok_or_else(|| JCloneError::Usage(format!("missing value for {option}")))
+}
+
+fn no_more_args(mut args: impl Iterator<Item = String>) -> JCloneResult<()> {
+    match args.next() {
+        Some(arg) => Err(unexpected(arg)),
+        None => Ok(()),
+    }
+}
+
+/// Handles the `--host H` and `PATTERN` arguments of commands that work on many checkouts.
+fn parse_filter_arg(
+    arg: String,
+    args: &mut impl Iterator<Item = String>,
+    filter: &mut Filter,
+) -> JCloneResult<()> {
+    match arg.as_str() {
+        "--host" => filter.host = Some(option_value(args, "--host")?),
+        _ if arg.starts_with('-') || filter.path.is_some() => return Err(unexpected(arg)),
+        _ => filter.path = Some(arg),
+    }
+
+    Ok(())
+}
+
+fn parse_list(mut args: impl Iterator<Item = String>) -> JCloneResult<Command> {
+    let mut filter = Filter::default();
+    let mut format = ListFormat::Table;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => format = ListFormat::Json,
+            "--paths" => format = ListFormat::Paths,
+            _ => parse_filter_arg(arg, &mut args, &mut filter)?,
+        }
+    }
+
+    Ok(Command::List { filter, format })
+}
+
+fn parse_status(mut args: impl Iterator<Item = String>) -> JCloneResult<Command> {
+    let mut filter = Filter::default();
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => parse_filter_arg(arg, &mut args, &mut filter)?,
+        }
+    }
+
+    Ok(Command::Status { filter, json })
+}
+
+fn parse_index(mut args: impl Iterator<Item = String>) -> JCloneResult<IndexCommand> {
+    let subcommand = args
+        .next()
+        .ok_or_else(|| JCloneError::Usage(String::from("missing index subcommand")))?;
+
+    match subcommand.as_str() {
+        "rebuild" => no_more_args(args).map(|_| IndexCommand::Rebuild),
+        _ => Err(JCloneError::Usage(format!(
+            "unknown index subcommand: {subcommand}"
+        ))),
+    }
+}
+
+fn parse_config(mut args: impl Iterator<Item = String>) -> JCloneResult<ConfigCommand> {
+    let subcommand = args
+        .next()
+        .ok_or_else(|| JCloneError::Usage(String::from("missing config subcommand")))?;
+
+    match subcommand.as_str() {
+        "check" => no_more_args(args).map(|_| ConfigCommand::Check),
+        "schema" => no_more_args(args).map(|_| ConfigCommand::Schema),
+        "show" => {
+            let mut host = None;
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--host" => host = Some(option_value(&mut args, "--host")?),
+                    _ => return Err(unexpected(arg)),
+                }
+            }
+
+            Ok(ConfigCommand::Show { host })
+        }
+        "init" => {
+            let mut local = false;
+
+            for arg in args {
+                match arg.as_str() {
+                    "--local" => local = true,
+                    _ => return Err(unexpected(arg)),
+                }
+            }
+
+            Ok(ConfigCommand::Init { local })
+        }
+        "migrate" => {
+            let mut write = false;
+
+            for arg in args {
+                match arg.as_str() {
+                    "--write" => write = true,
+                    _ => return Err(unexpected(arg)),
+                }
+            }
+
+            Ok(ConfigCommand::Migrate { write })
+        }
+        _ => Err(JCloneError::Usage(format!(
+            "unknown config subcommand: {subcommand}"
+        ))),
+    }
+}
+
+fn jobs_value(args: &mut impl Iterator<Item = String>) -> JCloneResult<usize> {
+    let value = option_value(args, "--jobs")?;
+
+    value
+        .parse()
+        .ok()
+        .filter(|jobs| *jobs > 0)
+        .ok_or_else(|| JCloneError::Usage(format!("invalid value for --jobs: {value}")))
+}
+
+fn parse_sync(mut args: impl Iterator<Item = String>) -> JCloneResult<Command> {
+    let mut filter = Filter::default();
+    let mut pull = false;
+    let mut jobs = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pull" => pull = true,
+            "--jobs" => jobs = Some(jobs_value(&mut args)?),
+            _ => parse_filter_arg(arg, &mut args, &mut filter)?,
+        }
+    }
+
+    Ok(Command::Sync { filter, pull, jobs })
+}
+
+fn parse_exec(mut args: impl Iterator<Item = String>) -> JCloneResult<Command> {
+    let mut filter = Filter::default();
+    let mut jobs = 1;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--host" => filter.host = Some(option_value(&mut args, "--host")?),
+            "--match" => filter.path = Some(option_value(&mut args, "--match")?),
+            "--jobs" => jobs = jobs_value(&mut args)?,
+            "--" => break,
+            _ => return Err(unexpected(arg)),
+        }
+    }
+
+    let command: Vec<String> = args.collect();
+
+    match command.is_empty() {
+        true => Err(JCloneError::Usage(String::from(
+            "missing command to run after --",
+        ))),
+        false => Ok(Command::Exec {
+            filter,
+            jobs,
+            command,
+        }),
+    }
+}
+
+fn parse_reorganise(args: impl Iterator<Item = String>) -> JCloneResult<Command> {
+    let mut yes = false;
+    let mut dirs = vec![];
+
+    for arg in args {
+        match arg.as_str() {
+            "-y" | "--yes" => yes = true,
+            _ if arg.starts_with('-') => return Err(unexpected(arg)),
+            _ => dirs.push(PathBuf::from(arg)),
+        }
+    }
+
+    Ok(Command::Reorganise { yes, dirs })
+}
+
+fn parse_adopt(args: impl Iterator<Item = String>) -> JCloneResult<Command> {
+    let mut dirs = vec![];
+    let mut recursive = false;
+    let mut symlink = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "-r" | "--recursive" => recursive = true,
+            "--symlink" => symlink = true,
+            _ if arg.starts_with('-') => return Err(unexpected(arg)),
+            _ => dirs.push(PathBuf::from(arg)),
+        }
+    }
+
+    match dirs.is_empty() {
+        true => Err(JCloneError::Usage(String::from("missing argument: dir"))),
+        false => Ok(Command::Adopt {
+            dirs,
+            recursive,
+            symlink,
+        }),
+    }
+}
+
+fn parse_rm(args: impl Iterator<Item = String>) -> JCloneResult<Command> {
+    let mut query = None;
+    let mut force = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "-f" | "--force" => force = true,
+            _ if arg.starts_with('-') || query.is_some() => return Err(unexpected(arg)),
+            _ => query = Some(arg),
+        }
+    }
+
+    let query =
+        query.ok_or_else(|| JCloneError::Usage(String::from("missing argument: repository")))?;
+
+    Ok(Command::Rm { query, force })
+}
+
+/// Parses an age like `90d`, `12w` or `1y` into seconds.
+fn parse_age(value: &str) -> Option<u64> {
+    let (number, days) = [('d', 1), ('w', 7), ('y', 365)]
+        .into_iter()
+        .find_map(|(unit, days)| Some((value.strip_suffix(unit)?, days)))?;
+
+    number.parse::<u64>().ok()?.checked_mul(days * 24 * 60 * 60)
+}
+
+fn parse_prune(mut args: impl Iterator<Item = String>) -> JCloneResult<Command> {
+    let mut filter = Filter::default();
+    let mut older_than = None;
+    let mut archive = None;
+    let mut yes = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--older-than" => {
+                let value = option_value(&mut args, "--older-than")?;
+                older_than = Some(parse_age(&value).ok_or_else(|| {
+                    JCloneError::Usage(format!("invalid value for --older-than: {value}"))
+                })?);
+            }
+            "--archive" => archive = Some(PathBuf::from(option_value(&mut args, "--archive")?)),
+            "-y" | "--yes" => yes = true,
+            _ => parse_filter_arg(arg, &mut args, &mut filter)?,
+        }
+    }
+
+    let older_than = older_than
+        .ok_or_else(|| JCloneError::Usage(String::from("missing option: --older-than")))?;
+
+    Ok(Command::Prune {
+        filter,
+        older_than,
+        archive,
+        yes,
+    })
+}
+
+impl Command {
+    /// Parses the arguments following the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> JCloneResult<Self> {
+        let mut args = args.into_iter();
+
+        let first = args
+            .next()
+            .ok_or_else(|| JCloneError::Usage(String::from("missing argument: repository")))?;
+
+        match first.as_str() {
+            "-h" | "--help" | "help" => no_more_args(args).map(|_| Command::Help),
+            "config" => parse_config(args).map(Command::Config),
+            "list" => parse_list(args),
+            "status" => parse_status(args),
+            "sync" => parse_sync(args),
+            "exec" => parse_exec(args),
+            "reorganise" | "reorganize" => parse_reorganise(args),
+            "adopt" => parse_adopt(args),
+            "rm" => parse_rm(args),
+            "prune" => parse_prune(args),
+            "index" => parse_index(args).map(Command::Index),
+            "path" => {
+                let query = args
+                    .next()
+                    .ok_or_else(|| JCloneError::Usage(String::from("missing argument: query")))?;
+
+                no_more_args(args).map(|_| Command::Path { query })
+            }
+            "-n" | "--dry-run" => {
+                let repo_str = args.next().ok_or_else(|| {
+                    JCloneError::Usage(String::from("missing argument: repository"))
+                })?;
+
+                no_more_args(args).map(|_| Command::Clone {
+                    repo_str,
+                    dry_run: true,
+                })
+            }
+            _ if first.starts_with('-') => Err(unexpected(first)),
+            _ => no_more_args(args).map(|_| Command::Clone {
+                repo_str: first,
+                dry_run: false,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> JCloneResult<Command> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[rstest]
+    #[case::clone(&["git@example.com:my_repo"], Command::Clone { repo_str: String::from("git@example.com:my_repo"), dry_run: false })]
+    #[case::clone_dry_run(&["--dry-run", "git@example.com:my_repo"], Command::Clone { repo_str: String::from("git@example.com:my_repo"), dry_run: true })]
+    #[case::help(&["--help"], Command::Help)]
+    #[case::list(&["list"], Command::List { filter: Filter::default(), format: ListFormat::Table })]
+    #[case::list_filtered(
+        &["list", "--host", "github.com", "--json", "rust-lang/*"],
+        Command::List {
+            filter: Filter { host: Some(String::from("github.com")), path: Some(String::from("rust-lang/*")) },
+            format: ListFormat::Json,
+        }
+    )]
+    #[case::list_paths(&["list", "--paths"], Command::List { filter: Filter::default(), format: ListFormat::Paths })]
+    #[case::status(&["status"], Command::Status { filter: Filter::default(), json: false })]
+    #[case::status_filtered(
+        &["status", "--json", "acme/*", "--host", "github.com"],
+        Command::Status {
+            filter: Filter { host: Some(String::from("github.com")), path: Some(String::from("acme/*")) },
+            json: true,
+        }
+    )]
+    #[case::sync(&["sync"], Command::Sync { filter: Filter::default(), pull: false, jobs: None })]
+    #[case::sync_pull(
+        &["sync", "--pull", "--jobs", "8", "--host", "github.com"],
+        Command::Sync {
+            filter: Filter { host: Some(String::from("github.com")), path: None },
+            pull: true,
+            jobs: Some(8),
+        }
+    )]
+    #[case::exec(
+        &["exec", "--", "cargo", "update"],
+        Command::Exec { filter: Filter::default(), jobs: 1, command: vec![String::from("cargo"), String::from("update")] }
+    )]
+    #[case::exec_filtered(
+        &["exec", "--match", "acme/*", "--jobs", "4", "--", "git", "--version"],
+        Command::Exec {
+            filter: Filter { host: None, path: Some(String::from("acme/*")) },
+            jobs: 4,
+            command: vec![String::from("git"), String::from("--version")],
+        }
+    )]
+    #[case::reorganise(&["reorganise"], Command::Reorganise { yes: false, dirs: vec![] })]
+    #[case::reorganize_yes(
+        &["reorganize", "--yes", "/home/ferris/code"],
+        Command::Reorganise { yes: true, dirs: vec![PathBuf::from("/home/ferris/code")] }
+    )]
+    #[case::adopt(
+        &["adopt", "app", "api"],
+        Command::Adopt { dirs: vec![PathBuf::from("app"), PathBuf::from("api")], recursive: false, symlink: false }
+    )]
+    #[case::adopt_recursive_symlink(
+        &["adopt", "--recursive", "--symlink", "projects"],
+        Command::Adopt { dirs: vec![PathBuf::from("projects")], recursive: true, symlink: true }
+    )]
+    #[case::rm(&["rm", "team/app"], Command::Rm { query: String::from("team/app"), force: false })]
+    #[case::rm_force(&["rm", "team/app", "--force"], Command::Rm { query: String::from("team/app"), force: true })]
+    #[case::prune(
+        &["prune", "--older-than", "90d"],
+        Command::Prune { filter: Filter::default(), older_than: 90 * 24 * 60 * 60, archive: None, yes: false }
+    )]
+    #[case::prune_archive(
+        &["prune", "--older-than", "2w", "--archive", "/mnt/archive", "--yes", "acme/*"],
+        Command::Prune {
+            filter: Filter { host: None, path: Some(String::from("acme/*")) },
+            older_than: 14 * 24 * 60 * 60,
+            archive: Some(PathBuf::from("/mnt/archive")),
+            yes: true,
+        }
+    )]
+    #[case::path(&["path", "rustl"], Command::Path { query: String::from("rustl") })]
+    #[case::index_rebuild(&["index", "rebuild"], Command::Index(IndexCommand::Rebuild))]
+    #[case::config_check(&["config", "check"], Command::Config(ConfigCommand::Check))]
+    #[case::config_show(&["config", "show"], Command::Config(ConfigCommand::Show { host: None }))]
+    #[case::config_show_host(
+        &["config", "show", "--host", "example.com"],
+        Command::Config(ConfigCommand::Show { host: Some(String::from("example.com")) })
+    )]
+    #[case::config_schema(&["config", "schema"], Command::Config(ConfigCommand::Schema))]
+    #[case::config_migrate(&["config", "migrate"], Command::Config(ConfigCommand::Migrate { write: false }))]
+    #[case::config_migrate_write(&["config", "migrate", "--write"], Command::Config(ConfigCommand::Migrate { write: true }))]
+    #[case::config_init(&["config", "init"], Command::Config(ConfigCommand::Init { local: false }))]
+    #[case::config_init_local(&["config", "init", "--local"], Command::Config(ConfigCommand::Init { local: true }))]
+    fn test_parse_valid_arguments(#[case] args: &[&str], #[case] expected: Command) {
+        assert_eq!(parse(args).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::empty(&[])]
+    #[case::unknown_flag(&["--verbose"])]
+    #[case::dry_run_without_repository(&["--dry-run"])]
+    #[case::extra_argument(&["git@example.com:my_repo", "somewhere"])]
+    #[case::missing_config_subcommand(&["config"])]
+    #[case::unknown_config_subcommand(&["config", "frobnicate"])]
+    #[case::missing_host_value(&["config", "show", "--host"])]
+    #[case::missing_index_subcommand(&["index"])]
+    #[case::path_without_query(&["path"])]
+    #[case::list_two_patterns(&["list", "acme/*", "rust-lang/*"])]
+    #[case::list_unknown_flag(&["list", "--long"])]
+    #[case::status_paths(&["status", "--paths"])]
+    #[case::sync_zero_jobs(&["sync", "--jobs", "0"])]
+    #[case::sync_invalid_jobs(&["sync", "--jobs", "many"])]
+    #[case::exec_without_command(&["exec", "--match", "acme/*", "--"])]
+    #[case::exec_without_separator(&["exec", "cargo", "update"])]
+    #[case::reorganise_unknown_flag(&["reorganise", "--force"])]
+    #[case::adopt_without_dir(&["adopt", "--recursive"])]
+    #[case::rm_without_repository(&["rm", "--force"])]
+    #[case::rm_two_repositories(&["rm", "team/app", "team/api"])]
+    #[case::prune_without_age(&["prune"])]
+    #[case::prune_unknown_unit(&["prune", "--older-than", "3m"])]
+    #[case::prune_no_number(&["prune", "--older-than", "d"])]
+    fn test_parse_invalid_arguments(#[case] args: &[&str]) {
+        assert!(matches!(parse(args), Err(JCloneError::Usage(_))));
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::{self, IsTerminal, Write};
+
+use crate::errors::{JCloneError, JCloneResult};
+
+pub mod adopt;
+pub mod config;
+pub mod exec;
+pub mod index;
+pub mod list;
+pub mod path;
+pub mod prune;
+pub mod reorganise;
+pub mod rm;
+pub mod status;
+pub mod sync;
+
+/// `n` followed by `one` or `many` to match, e.g. `1 repository` or `3 repositories`.
+pub fn plural(n: usize, one: &str, many: &str) -> String {
+    match n {
+        1 => format!("1 {one}"),
+        n => format!("{n} {many}"),
+    }
+}
+
+/// Asks a yes or no question on stderr, unless `yes` already answered it. Without a terminal
+/// to ask on, the answer is no.
+pub fn confirm(question: &str, yes: bool) -> JCloneResult<()> {
+    if yes {
+        return Ok(());
+    }
+
+    if !io::stdin().is_terminal() {
+        return Err(JCloneError::NotConfirmed);
+    }
+
+    eprint!("{question} [y/N] ");
+    let _ = io::stderr().flush();
+
+    let mut answer = String::new();
+    let _ = io::stdin().read_line(&mut answer);
+
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(JCloneError::NotConfirmed),
+    }
+}
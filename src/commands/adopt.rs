@@ -0,0 +1,179 @@
+use std::fs;
+use std::os::unix;
+use std::path::{self, PathBuf};
+
+use crate::commands::reorganise::{self, Move};
+use crate::configuration::Configuration;
+use crate::errors::{JCloneError, JCloneResult};
+use crate::git::Git;
+use crate::index::Index;
+use crate::layout;
+use crate::user_configuration::UserConfiguration;
+use crate::workspace;
+
+/// The checkouts to adopt: each of `dirs`, or every checkout under them if `recursive`.
+fn checkout_dirs(dirs: &[PathBuf], recursive: bool) -> JCloneResult<Vec<PathBuf>> {
+    let mut found = vec![];
+
+    for dir in dirs {
+        let dir = path::absolute(dir).map_err(JCloneError::CurrentDirectory)?;
+
+        match recursive {
+            true => found.extend(workspace::find_checkout_dirs(&dir)),
+            false => found.push(dir),
+        }
+    }
+
+    Ok(found)
+}
+
+/// Works out where the checkout at `from` belongs from its `origin` remote.
+fn plan(git: &Git, user_config: &UserConfiguration, from: PathBuf) -> Result<Move, String> {
+    if !from.join(".git").exists() {
+        return Err(String::from("it isn't a git checkout"));
+    }
+
+    let url = git
+        .remote_url(&from)
+        .map_err(|err| err.to_string())?
+        .ok_or_else(|| String::from("it has no origin remote"))?;
+
+    let destination = layout::destination(user_config, &url).map_err(|err| err.to_string())?;
+
+    Ok(Move {
+        from,
+        url,
+        destination,
+    })
+}
+
+/// Moves the checkout to its new directory, or links the new directory to it.
+fn adopt(planned: &Move, symlink: bool) -> JCloneResult<()> {
+    let from = &planned.from;
+    let to = &planned.destination.target_dir;
+    let move_error = |source| JCloneError::AdoptMove {
+        from: from.to_owned(),
+        to: to.to_owned(),
+        source,
+    };
+
+    if !symlink {
+        workspace::move_dir(from, to).map_err(move_error)?;
+        println!("📂 Moved {} to {}", from.display(), to.display());
+        return Ok(());
+    }
+
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).map_err(move_error)?;
+    }
+
+    unix::fs::symlink(from, to).map_err(move_error)?;
+    println!("🔗 Linked {} to {}", to.display(), from.display());
+
+    Ok(())
+}
+
+pub fn run(dirs: Vec<PathBuf>, recursive: bool, symlink: bool) -> JCloneResult<()> {
+    let user_config = UserConfiguration::try_load()?;
+    let base_config = Configuration::try_for_host(user_config.to_owned(), &String::new())?;
+    let git = Git::local(&base_config);
+
+    let mut moves = vec![];
+    let mut failed = 0;
+
+    for from in checkout_dirs(&dirs, recursive)? {
+        match plan(&git, &user_config, from.to_owned()) {
+            Ok(planned) => moves.push(planned),
+            Err(reason) => {
+                println!("❌ Can't adopt {}: {reason}", from.display());
+                failed += 1;
+            }
+        }
+    }
+
+    let mut adopted: Vec<&Move> = vec![];
+
+    for planned in &moves {
+        let result = if planned.destination.target_dir == planned.from {
+            println!(
+                "✅ {} is already where jclone would clone it",
+                planned.from.display()
+            );
+            Ok(())
+        } else if let Some(reason) = reorganise::collision(planned, &moves) {
+            Err(format!("Can't adopt {}: {reason}", planned.from.display()))
+        } else {
+            adopt(planned, symlink).map_err(|err| err.to_string())
+        };
+
+        match result {
+            Ok(()) => adopted.push(planned),
+            Err(message) => {
+                println!("❌ {message}");
+                failed += 1;
+            }
+        }
+    }
+
+    Index::update_default(|index| reorganise::record_moves(index, &adopted));
+
+    match failed {
+        0 => {
+            println!("🎉 Done!");
+            Ok(())
+        }
+        n => Err(JCloneError::AdoptFailed(n, n + adopted.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::Destination;
+    use crate::repository::Repository;
+
+    use super::*;
+
+    #[test]
+    fn test_checkout_dirs_recursive() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for checkout in ["team/app/.git", "team/api/.git", "notes"] {
+            fs::create_dir_all(dir.path().join(checkout)).unwrap();
+        }
+
+        let projects = vec![dir.path().to_owned()];
+
+        assert_eq!(checkout_dirs(&projects, false).unwrap(), projects);
+        assert_eq!(
+            checkout_dirs(&projects, true).unwrap(),
+            vec![dir.path().join("team/api"), dir.path().join("team/app")]
+        );
+    }
+
+    #[test]
+    fn test_adopt_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("projects/app");
+        let to = dir.path().join("src/example.com/team/app");
+        fs::create_dir_all(from.join(".git")).unwrap();
+
+        let planned = Move {
+            from: from.to_owned(),
+            url: String::from("git@example.com:team/app.git"),
+            destination: Destination {
+                repository: Repository {
+                    host: String::from("example.com"),
+                    path: String::from("team/app"),
+                },
+                variant: None,
+                target_dir: to.to_owned(),
+            },
+        };
+
+        adopt(&planned, true).unwrap();
+
+        assert_eq!(fs::read_link(&to).unwrap(), from);
+        assert!(to.join(".git").is_dir());
+        assert!(from.join(".git").is_dir());
+    }
+}
@@ -0,0 +1,705 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    cli::ConfigCommand,
+    configuration::Configuration,
+    errors::{JCloneError, JCloneResult},
+    migration,
+    user_configuration::{CONFIG_FILE_NAME, UserConfiguration, UserHostConfiguration},
+};
+
+const STARTER_CONFIG: &str = r#"# jclone configuration
+# See https://github.com/jacobwalkr/jclone#configuration for all the options.
+
+version = 1
+
+# Directory to clone into. Defaults to "$HOME/src".
+# base_dir = "/home/ferris/src"
+
+# Resolve a relative base_dir against the directory containing this file.
+# relative_base_dir = false
+
+# Include the host (e.g. "github.com") as a directory.
+# use_host_dir = true
+
+# Use the full path of the repository rather than just its name.
+# use_full_path = true
+
+# What to print: "default", "git-only", "no-git" or "quiet".
+# output = "default"
+
+# The git executable to run.
+# git_executable = "git"
+
+# Convert repository URLs to "ssh" or "https" before cloning.
+# prefer_protocol = "ssh"
+
+# Commands to run in each new checkout. {path}, {host} and {name} are replaced
+# with the checkout directory, repository host and repository name.
+# post_clone = ["pre-commit install"]
+
+# After cloning from a web link to a branch, tag or commit, check it out.
+# checkout_linked_ref = false
+
+# Settings for a particular host override the ones above.
+# [[variant]]
+# host = "git.example.com"
+# base_dir = "/home/ferris/work"
+"#;
+
+pub fn run(command: ConfigCommand) -> JCloneResult<()> {
+    match command {
+        ConfigCommand::Check => check(),
+        ConfigCommand::Show { host } => show(host),
+        ConfigCommand::Init { local } => init(local),
+        ConfigCommand::Schema => {
+            print!("{}", UserConfiguration::json_schema());
+            Ok(())
+        }
+        ConfigCommand::Migrate { write } => migrate(write),
+    }
+}
+
+fn executable_exists(executable: &str) -> bool {
+    if executable.contains('/') {
+        return Path::new(executable).is_file();
+    }
+
+    env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(executable).is_file()))
+}
+
+/// Finds problems in a single config file that parsing alone won't catch.
+fn config_problems(user_config: &UserConfiguration) -> Vec<String> {
+    let mut problems = vec![];
+    let mut seen_hosts: Vec<&String> = vec![];
+
+    for (index, variant) in user_config.variants.iter().enumerate() {
+        if variant.host.is_empty() {
+            problems.push(format!(
+                "variant {} is unreachable: host is empty",
+                index + 1
+            ));
+        } else if seen_hosts.contains(&&variant.host) {
+            problems.push(format!(
+                "variant {} is unreachable: an earlier variant already matches {}",
+                index + 1,
+                variant.host
+            ));
+        }
+
+        seen_hosts.push(&variant.host);
+    }
+
+    let executables = user_config.git_executable.iter().chain(
+        user_config
+            .variants
+            .iter()
+            .filter_map(|v| v.git_executable.as_ref()),
+    );
+
+    for executable in executables {
+        if !executable_exists(executable) {
+            problems.push(format!("git_executable not found: {executable}"));
+        }
+    }
+
+    problems
+}
+
+/// Finds variants in `user_config` with every setting overridden by variants for the same host
+/// in `nearer` config files, which take precedence.
+fn shadowed_variants(
+    user_config: &UserConfiguration,
+    nearer: &[(&Path, UserConfiguration)],
+) -> Vec<String> {
+    let mut problems = vec![];
+
+    for (index, variant) in user_config.variants.iter().enumerate() {
+        let keys = variant.set_keys();
+
+        // Later variants for the same host are already unreachable within the file
+        if keys.is_empty()
+            || user_config.variants[..index]
+                .iter()
+                .any(|earlier| earlier.host == variant.host)
+        {
+            continue;
+        }
+
+        let overriding: Vec<(&Path, &UserHostConfiguration)> = nearer
+            .iter()
+            .filter_map(|(path, nearer_config)| {
+                nearer_config
+                    .variants
+                    .iter()
+                    .find(|nearer_variant| nearer_variant.host == variant.host)
+                    .map(|nearer_variant| (*path, nearer_variant))
+            })
+            .collect();
+
+        let shadowed = keys.iter().all(|key| {
+            overriding
+                .iter()
+                .any(|(_, nearer_variant)| nearer_variant.set_keys().contains(key))
+        });
+
+        if shadowed {
+            let paths: Vec<String> = overriding
+                .iter()
+                .map(|(path, _)| path.display().to_string())
+                .collect();
+
+            problems.push(format!(
+                "variant {} is unreachable: {} overrides every setting in it for {}",
+                index + 1,
+                paths.join(", "),
+                variant.host
+            ));
+        }
+    }
+
+    problems
+}
+
+fn check() -> JCloneResult<()> {
+    let paths = UserConfiguration::try_config_paths()?;
+
+    if paths.is_empty() {
+        println!("No configuration files found, using defaults");
+        return Ok(());
+    }
+
+    let user_config_path = UserConfiguration::user_config_path()?;
+    let loaded: Vec<(&Path, JCloneResult<UserConfiguration>)> = paths
+        .iter()
+        .map(|path| (path.as_path(), UserConfiguration::try_load_file(path)))
+        .collect();
+
+    // What each file adds as a layer, for finding variants that nearer files shadow
+    let layers: Vec<(&Path, UserConfiguration)> = loaded
+        .iter()
+        .map(|(path, user_config)| {
+            let layer = match user_config {
+                Ok(user_config) if *path != user_config_path => {
+                    user_config.to_owned().without_user_only_keys()
+                }
+                Ok(user_config) => user_config.to_owned(),
+                Err(_) => UserConfiguration::default(),
+            };
+
+            (*path, layer)
+        })
+        .collect();
+
+    let mut problem_count = 0;
+
+    for (index, (path, user_config)) in loaded.iter().enumerate() {
+        let problems = match user_config {
+            Ok(user_config) => {
+                let mut problems = config_problems(user_config);
+
+                if *path != user_config_path {
+                    problems.extend(user_config.user_only_keys().into_iter().map(|key| {
+                        format!(
+                            "{key} is ignored here, it's only read from {}",
+                            user_config_path.display()
+                        )
+                    }));
+                }
+
+                problems.extend(shadowed_variants(&layers[index].1, &layers[index + 1..]));
+                problems
+            }
+            Err(err) => vec![err.to_string()],
+        };
+
+        match problems.is_empty() {
+            true => println!("✅ {}", path.display()),
+            false => problems
+                .iter()
+                .for_each(|problem| println!("❌ {}: {problem}", path.display())),
+        }
+
+        problem_count += problems.len();
+    }
+
+    match problem_count {
+        0 => Ok(()),
+        n => Err(JCloneError::ConfigurationCheck(n)),
+    }
+}
+
+enum Origin<'a> {
+    Default,
+    File(&'a Path),
+    Variant(&'a Path, &'a String),
+}
+
+impl std::fmt::Display for Origin<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Variant(path, host) => write!(f, "{} [variant {host}]", path.display()),
+        }
+    }
+}
+
+struct Layers<'a> {
+    layers: &'a [(PathBuf, UserConfiguration)],
+    /// The variant for the host in each layer that has one, nearest first.
+    variants: Vec<(&'a Path, &'a UserHostConfiguration)>,
+}
+
+impl<'a> Layers<'a> {
+    fn new(layers: &'a [(PathBuf, UserConfiguration)], host: &str) -> Self {
+        let variants = layers
+            .iter()
+            .rev()
+            .filter_map(|(path, user_config)| {
+                user_config
+                    .variants
+                    .iter()
+                    .find(|variant| variant.host == host)
+                    .map(|variant| (path.as_path(), variant))
+            })
+            .collect();
+
+        Self { layers, variants }
+    }
+
+    /// Works out where a value comes from, following the same precedence as
+    /// `Configuration::from_user_configuration`.
+    fn origin(
+        &self,
+        in_variant: impl Fn(&UserHostConfiguration) -> bool,
+        in_config: impl Fn(&UserConfiguration) -> bool,
+    ) -> Origin<'a> {
+        if let Some((path, variant)) = self.variants.iter().find(|(_, v)| in_variant(v)) {
+            return Origin::Variant(path, &variant.host);
+        }
+
+        self.layers
+            .iter()
+            .rev()
+            .find(|(_, user_config)| in_config(user_config))
+            .map_or(Origin::Default, |(path, _)| Origin::File(path))
+    }
+}
+
+/// Formats a value as TOML, or `None` for an unset optional value.
+fn toml_value(value: impl Serialize) -> Option<String> {
+    toml::Value::try_from(value).ok().map(|v| v.to_string())
+}
+
+/// Formats a table on one line, or `None` if it's empty.
+fn inline_table(table: &BTreeMap<String, String>) -> Option<String> {
+    let entries: Vec<String> = table
+        .iter()
+        .filter_map(|(key, value)| toml_value(value).map(|value| format!("{key} = {value}")))
+        .collect();
+
+    (!entries.is_empty()).then(|| format!("{{ {} }}", entries.join(", ")))
+}
+
+fn show(host: Option<String>) -> JCloneResult<()> {
+    let layers = UserConfiguration::try_load_layers()?;
+    let home = env::var("HOME").map_err(JCloneError::Environment)?;
+    let host = host.unwrap_or_default();
+
+    let user_config = layers
+        .iter()
+        .fold(UserConfiguration::default(), |config, (_, layer)| {
+            config.merge(layer.to_owned())
+        });
+    let config = Configuration::from_user_configuration(user_config, &host, home)?;
+    let layers = Layers::new(&layers, &host);
+
+    let entries = [
+        (
+            "base_dir",
+            toml_value(&config.base_dir),
+            layers.origin(|v| v.base_dir.is_some(), |c| c.base_dir.is_some()),
+        ),
+        (
+            "use_host_dir",
+            toml_value(config.use_host_dir),
+            layers.origin(|v| v.use_host_dir.is_some(), |c| c.use_host_dir.is_some()),
+        ),
+        (
+            "use_full_path",
+            toml_value(config.use_full_path),
+            layers.origin(|v| v.use_full_path.is_some(), |c| c.use_full_path.is_some()),
+        ),
+        (
+            "output",
+            toml_value(&config.output_style),
+            layers.origin(|v| v.output_style.is_some(), |c| c.output_style.is_some()),
+        ),
+        (
+            "git_executable",
+            toml_value(&config.git_executable),
+            layers.origin(
+                |v| v.git_executable.is_some(),
+                |c| c.git_executable.is_some(),
+            ),
+        ),
+        (
+            "prefer_protocol",
+            toml_value(config.prefer_protocol),
+            layers.origin(
+                |v| v.prefer_protocol.is_some(),
+                |c| c.prefer_protocol.is_some(),
+            ),
+        ),
+        (
+            "ssh_user",
+            toml_value(&config.ssh_user),
+            layers.origin(|v| v.ssh_user.is_some(), |c| c.ssh_user.is_some()),
+        ),
+        (
+            "user_name",
+            toml_value(&config.user_name),
+            layers.origin(|v| v.user_name.is_some(), |_| false),
+        ),
+        (
+            "user_email",
+            toml_value(&config.user_email),
+            layers.origin(|v| v.user_email.is_some(), |_| false),
+        ),
+        (
+            "signing_key",
+            toml_value(&config.signing_key),
+            layers.origin(|v| v.signing_key.is_some(), |_| false),
+        ),
+        (
+            "post_clone",
+            toml_value(&config.post_clone),
+            layers.origin(|v| v.post_clone.is_some(), |c| c.post_clone.is_some()),
+        ),
+        (
+            "checkout_linked_ref",
+            toml_value(config.checkout_linked_ref),
+            layers.origin(
+                |v| v.checkout_linked_ref.is_some(),
+                |c| c.checkout_linked_ref.is_some(),
+            ),
+        ),
+        (
+            "ssh_key",
+            toml_value(&config.ssh_key),
+            layers.origin(|v| v.ssh_key.is_some(), |_| false),
+        ),
+        (
+            "ssh_options",
+            toml_value(&config.ssh_options),
+            layers.origin(|v| v.ssh_options.is_some(), |_| false),
+        ),
+        (
+            "env",
+            inline_table(&config.env),
+            layers.origin(|v| v.env.is_some(), |_| false),
+        ),
+        (
+            "persist_ssh_command",
+            toml_value(config.persist_ssh_command),
+            layers.origin(|v| v.persist_ssh_command.is_some(), |_| false),
+        ),
+        (
+            "forge",
+            toml_value(config.forge),
+            layers.origin(|v| v.forge.is_some(), |_| false),
+        ),
+        (
+            "allowed_hosts",
+            toml_value(&config.allowed_hosts),
+            layers.origin(|_| false, |c| c.allowed_hosts.is_some()),
+        ),
+        (
+            "denied_hosts",
+            toml_value(&config.denied_hosts),
+            layers.origin(|_| false, |c| c.denied_hosts.is_some()),
+        ),
+        (
+            "pre_clone",
+            toml_value(&config.pre_clone),
+            layers.origin(|_| false, |c| c.pre_clone.is_some()),
+        ),
+        (
+            "local_host",
+            toml_value(&config.local_host),
+            layers.origin(|_| false, |c| c.local_host.is_some()),
+        ),
+        (
+            "local_path_prefixes",
+            toml_value(&config.local_path_prefixes),
+            layers.origin(|_| false, |c| c.local_path_prefixes.is_some()),
+        ),
+        (
+            "resolve_ssh_aliases",
+            toml_value(config.resolve_ssh_aliases),
+            layers.origin(|_| false, |c| c.resolve_ssh_aliases.is_some()),
+        ),
+    ];
+
+    let entries: Vec<_> = entries
+        .into_iter()
+        .filter_map(|(key, value, origin)| value.map(|value| (key, value, origin)))
+        .collect();
+
+    if !host.is_empty() {
+        println!("# Effective configuration for {host}");
+    }
+
+    let width = entries
+        .iter()
+        .map(|(key, value, _)| key.len() + value.len())
+        .max()
+        .unwrap_or_default();
+
+    for (key, value, origin) in entries {
+        let padding = width - key.len() - value.len();
+        println!("{key} = {value}{:padding$}  # {origin}", "");
+    }
+
+    Ok(())
+}
+
+fn migrate(write: bool) -> JCloneResult<()> {
+    let mut pending = false;
+
+    for path in UserConfiguration::try_config_paths()? {
+        let config_str = fs::read_to_string(&path)
+            .map_err(|err| JCloneError::ConfigurationFileLoad(path.to_owned(), err))?;
+
+        let mut migrated = migration::migrate(&config_str, &path)?;
+        migration::stamp_version(&mut migrated.document);
+        let migrated_str = migrated.document.to_string();
+
+        // Make sure we'd be writing something jclone can read back
+        UserConfiguration::try_from(migrated_str.to_owned())
+            .map_err(|err| JCloneError::ConfigurationParse(path.to_owned(), err))?;
+
+        if migrated_str == config_str {
+            println!("✅ {} is up to date", path.display());
+            continue;
+        }
+
+        for warning in migrated.warnings {
+            println!("⚠️  {}: {warning}", path.display());
+        }
+
+        match write {
+            true => {
+                fs::write(&path, migrated_str)
+                    .map_err(|err| JCloneError::ConfigurationFileWrite(path.to_owned(), err))?;
+                println!(
+                    "📝 Migrated {} to version {}",
+                    path.display(),
+                    migration::CURRENT_VERSION
+                );
+            }
+            false => {
+                pending = true;
+                println!(
+                    "📝 {} would be rewritten as:\n{migrated_str}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    if pending {
+        println!("Run `jclone config migrate --write` to save changes");
+    }
+
+    Ok(())
+}
+
+fn init(local: bool) -> JCloneResult<()> {
+    let path = match local {
+        true => env::current_dir()
+            .map_err(JCloneError::CurrentDirectory)?
+            .join(CONFIG_FILE_NAME),
+        false => UserConfiguration::user_config_path()?,
+    };
+
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(STARTER_CONFIG.as_bytes()));
+
+    match written {
+        Ok(_) => {
+            println!("📝 Wrote starter configuration to {}", path.display());
+            Ok(())
+        }
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            Err(JCloneError::ConfigurationExists(path))
+        }
+        Err(err) => Err(JCloneError::ConfigurationFileWrite(path, err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starter_config_parses_when_uncommented() {
+        let uncommented: String = STARTER_CONFIG
+            .lines()
+            .map(|line| match line.strip_prefix("# ") {
+                Some(setting) if setting.contains(" = ") || setting.starts_with("[[") => setting,
+                _ => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let user_config = UserConfiguration::try_from(uncommented).unwrap();
+
+        assert!(user_config.base_dir.is_some());
+        assert_eq!(user_config.variants.len(), 1);
+    }
+
+    #[test]
+    fn test_config_problems_finds_unreachable_variants() {
+        let user_config = UserConfiguration::try_from(String::from(
+            r#"
+            [[variant]]
+            host = "example.com"
+
+            [[variant]]
+            host = "example.com"
+
+            [[variant]]
+            host = ""
+            "#,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            config_problems(&user_config),
+            vec![
+                "variant 2 is unreachable: an earlier variant already matches example.com",
+                "variant 3 is unreachable: host is empty",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shadowed_variants_across_files() {
+        let user_config = UserConfiguration::try_from(String::from(
+            r#"
+            [[variant]]
+            host = "example.com"
+            user_email = "ferris@example.com"
+
+            [[variant]]
+            host = "example.net"
+            user_email = "ferris@example.net"
+            ssh_key = "~/.ssh/id_example"
+            "#,
+        ))
+        .unwrap();
+        let nearer = UserConfiguration::try_from(String::from(
+            r#"
+            [[variant]]
+            host = "example.com"
+            user_email = "ferris@client-a.example.com"
+
+            [[variant]]
+            host = "example.net"
+            user_email = "ferris@client-a.example.net"
+            "#,
+        ))
+        .unwrap();
+        let nearer_path = Path::new("/home/ferris/work/.jclone.toml");
+
+        assert_eq!(
+            shadowed_variants(&user_config, &[(nearer_path, nearer)]),
+            vec![
+                "variant 1 is unreachable: /home/ferris/work/.jclone.toml overrides every setting in it for example.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_problems_finds_unknown_executables() {
+        let user_config = UserConfiguration::try_from(String::from(
+            r#"
+            git_executable = "/definitely/not/a/real/git"
+
+            [[variant]]
+            host = "example.com"
+            git_executable = "definitely-not-a-real-git"
+            "#,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            config_problems(&user_config),
+            vec![
+                "git_executable not found: /definitely/not/a/real/git",
+                "git_executable not found: definitely-not-a-real-git",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_origin_follows_configuration_precedence() {
+        let layers = vec![
+            (
+                PathBuf::from("/home/ferris/.jclone.toml"),
+                UserConfiguration {
+                    base_dir: Some(String::from("/home/ferris/code")),
+                    use_host_dir: Some(false),
+                    ..UserConfiguration::default()
+                },
+            ),
+            (
+                PathBuf::from("/home/ferris/work/.jclone.toml"),
+                UserConfiguration {
+                    use_host_dir: Some(true),
+                    variants: vec![UserHostConfiguration {
+                        host: String::from("example.com"),
+                        base_dir: Some(String::from("/home/ferris/work")),
+                        ..UserHostConfiguration::default()
+                    }],
+                    ..UserConfiguration::default()
+                },
+            ),
+        ];
+
+        let layers = Layers::new(&layers, "example.com");
+
+        assert_eq!(
+            layers
+                .origin(|v| v.base_dir.is_some(), |c| c.base_dir.is_some())
+                .to_string(),
+            "/home/ferris/work/.jclone.toml [variant example.com]"
+        );
+        assert_eq!(
+            layers
+                .origin(|v| v.use_host_dir.is_some(), |c| c.use_host_dir.is_some())
+                .to_string(),
+            "/home/ferris/work/.jclone.toml"
+        );
+        assert_eq!(
+            layers
+                .origin(|v| v.output_style.is_some(), |c| c.output_style.is_some())
+                .to_string(),
+            "default"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::commands::{self, list};
+use crate::errors::{JCloneError, JCloneResult};
+use crate::user_configuration::UserConfiguration;
+use crate::workspace::{self, Checkout, Filter};
+
+/// Runs `program` in the checkout, printing each line of its output prefixed with the
+/// checkout's name. Returns why it failed, if it did.
+fn run_in(checkout: &Checkout, program: &str, args: &[String]) -> Result<(), String> {
+    let name = checkout.name();
+
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(&checkout.dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| err.to_string())?;
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    // Each line is printed whole, so output from checkouts running at once doesn't mix mid-line
+    thread::scope(|scope| {
+        if let Some(stderr) = stderr {
+            scope.spawn(|| {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    eprintln!("{name}: {line}");
+                }
+            });
+        }
+
+        if let Some(stdout) = stdout {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                println!("{name}: {line}");
+            }
+        }
+    });
+
+    match child.wait() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(status.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+pub fn run(filter: Filter, jobs: usize, command: Vec<String>) -> JCloneResult<()> {
+    let user_config = UserConfiguration::try_load()?;
+    let checkouts = list::matching_checkouts(&user_config, &filter)?;
+
+    let Some((program, args)) = command.split_first() else {
+        return Err(JCloneError::Usage(String::from(
+            "missing command to run after --",
+        )));
+    };
+
+    let results =
+        workspace::map_parallel(&checkouts, jobs, |checkout| run_in(checkout, program, args));
+
+    let failures: Vec<(&Checkout, &String)> = checkouts
+        .iter()
+        .zip(&results)
+        .filter_map(|(checkout, result)| result.as_ref().err().map(|err| (checkout, err)))
+        .collect();
+
+    println!(
+        "📝 Succeeded in {} of {}",
+        checkouts.len() - failures.len(),
+        commands::plural(checkouts.len(), "repository", "repositories")
+    );
+
+    for (checkout, err) in &failures {
+        println!("❌ {}: {err}", checkout.name());
+    }
+
+    match failures.len() {
+        0 => Ok(()),
+        n => Err(JCloneError::ExecFailed(n, checkouts.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::success("true", &[], Ok(()))]
+    #[case::exit_status("sh", &["-c", "exit 3"], Err(String::from("exit status: 3")))]
+    fn test_run_in(
+        #[case] program: &str,
+        #[case] args: &[&str],
+        #[case] expected: Result<(), String>,
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let checkout = Checkout {
+            dir: PathBuf::from(dir.path()),
+            host: None,
+            path: String::from("team/app"),
+        };
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+
+        assert_eq!(run_in(&checkout, program, &args), expected);
+    }
+
+    #[test]
+    fn test_run_in_missing_program() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkout = Checkout {
+            dir: PathBuf::from(dir.path()),
+            host: None,
+            path: String::from("team/app"),
+        };
+
+        assert!(run_in(&checkout, "jclone-no-such-program", &[]).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::cli::IndexCommand;
+use crate::commands;
+use crate::configuration::Configuration;
+use crate::errors::JCloneResult;
+use crate::git::Git;
+use crate::index::{self, Index, IndexEntry};
+use crate::layout::Layout;
+use crate::repository::Repository;
+use crate::user_configuration::UserConfiguration;
+use crate::workspace;
+
+pub fn run(command: IndexCommand) -> JCloneResult<()> {
+    match command {
+        IndexCommand::Rebuild => rebuild(),
+    }
+}
+
+fn rebuild() -> JCloneResult<()> {
+    let user_config = UserConfiguration::try_load()?;
+    let index = Index::try_default()?;
+    let checkouts = workspace::find_checkouts(&Layout::try_all(&user_config)?);
+
+    // Rebuilding is the fix for an unreadable index, so start from scratch in that case
+    let previous = index.load().unwrap_or_default();
+    let mut entries = vec![];
+
+    for checkout in checkouts {
+        let host = checkout.host.to_owned().unwrap_or_default();
+        let config = Configuration::try_for_host(user_config.to_owned(), &host)?;
+        let url = Git::local(&config)
+            .remote_url(&checkout.dir)?
+            .unwrap_or_default();
+
+        // Layouts without a host directory don't say where the repository came from
+        let host = match checkout.host {
+            Some(host) => host,
+            None => Repository::try_from(&url)
+                .map(|repository| repository.host)
+                .unwrap_or_default(),
+        };
+
+        let cloned_at = previous
+            .iter()
+            .find(|entry| entry.dir == checkout.dir)
+            // The last change to .git is the best guess at when it was cloned
+            .map_or_else(
+                || index::git_modified_at(&checkout.dir),
+                |entry| entry.cloned_at,
+            );
+
+        let variant = user_config
+            .variants
+            .iter()
+            .find(|variant| variant.host == host)
+            .map(|variant| variant.host.to_owned());
+
+        entries.push(IndexEntry {
+            url,
+            repository: Repository {
+                host,
+                path: checkout.path,
+            },
+            dir: checkout.dir,
+            cloned_at,
+            variant,
+        });
+    }
+
+    let count = commands::plural(entries.len(), "repository", "repositories");
+    index.replace(entries)?;
+
+    println!("✅ Indexed {count} in {}", index.path().display());
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use crate::cli::ListFormat;
+use crate::errors::{JCloneError, JCloneResult};
+use crate::layout::Layout;
+use crate::user_configuration::UserConfiguration;
+use crate::workspace::{self, Checkout, Filter};
+
+/// The checkouts under every configured base directory that match `filter`.
+pub fn matching_checkouts(
+    user_config: &UserConfiguration,
+    filter: &Filter,
+) -> JCloneResult<Vec<Checkout>> {
+    let layouts = Layout::try_all(user_config)?;
+
+    Ok(workspace::find_checkouts(&layouts)
+        .into_iter()
+        .filter(|checkout| filter.matches(checkout))
+        .collect())
+}
+
+fn print_table(checkouts: &[Checkout]) {
+    let hosts: Vec<&str> = checkouts
+        .iter()
+        .map(|checkout| checkout.host.as_deref().unwrap_or("-"))
+        .collect();
+
+    let host_width = hosts
+        .iter()
+        .map(|host| host.len())
+        .max()
+        .unwrap_or_default();
+    let path_width = checkouts
+        .iter()
+        .map(|checkout| checkout.path.len())
+        .max()
+        .unwrap_or_default();
+
+    for (checkout, host) in checkouts.iter().zip(hosts) {
+        println!(
+            "{host:host_width$}  {:path_width$}  {}",
+            checkout.path,
+            checkout.dir.display()
+        );
+    }
+}
+
+pub fn run(filter: Filter, format: ListFormat) -> JCloneResult<()> {
+    let checkouts = matching_checkouts(&UserConfiguration::try_load()?, &filter)?;
+
+    match format {
+        ListFormat::Table => print_table(&checkouts),
+        ListFormat::Paths => {
+            for checkout in &checkouts {
+                println!("{}", checkout.dir.display());
+            }
+        }
+        ListFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&checkouts).map_err(JCloneError::JsonOutput)?
+        ),
+    }
+
+    Ok(())
+}
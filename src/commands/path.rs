@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+
+use crate::commands::list;
+use crate::errors::{JCloneError, JCloneResult};
+use crate::fuzzy::{self, Score};
+use crate::index::{self, Index};
+use crate::user_configuration::UserConfiguration;
+use crate::workspace::Filter;
+
+/// How many repositories to offer when the query is ambiguous.
+const MAX_CHOICES: usize = 10;
+
+struct Candidate {
+    dir: PathBuf,
+    name: String,
+    /// When it was cloned or last used, in seconds since the Unix epoch.
+    used_at: u64,
+}
+
+/// The repositories on disk and in the index, which also knows when each was cloned.
+fn candidates() -> JCloneResult<Vec<Candidate>> {
+    let mut candidates: BTreeMap<PathBuf, Candidate> =
+        list::matching_checkouts(&UserConfiguration::try_load()?, &Filter::default())?
+            .into_iter()
+            .map(|checkout| {
+                let candidate = Candidate {
+                    name: checkout.name(),
+                    used_at: index::git_modified_at(&checkout.dir),
+                    dir: checkout.dir.to_owned(),
+                };
+
+                (checkout.dir, candidate)
+            })
+            .collect();
+
+    for entry in Index::try_default()?.load()? {
+        if !entry.dir.join(".git").exists() {
+            continue;
+        }
+
+        let candidate = candidates
+            .entry(entry.dir.to_owned())
+            .or_insert_with(|| Candidate {
+                name: format!("{}/{}", entry.repository.host, entry.repository.path),
+                used_at: index::git_modified_at(&entry.dir),
+                dir: entry.dir,
+            });
+
+        candidate.used_at = candidate.used_at.max(entry.cloned_at);
+    }
+
+    Ok(candidates.into_values().collect())
+}
+
+/// Asks which of `choices` to use on stderr, so stdout can be captured by `$(...)`.
+fn choose<'a>(choices: &[&'a Candidate]) -> JCloneResult<&'a Candidate> {
+    for (number, candidate) in choices.iter().enumerate() {
+        eprintln!("{:>2}) {}", number + 1, candidate.name);
+    }
+
+    eprint!("Which one? [1-{}] ", choices.len());
+    let _ = io::stderr().flush();
+
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|_| JCloneError::InvalidChoice(String::new()))?;
+
+    let answer = answer.trim();
+
+    answer
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| choices.get(number.checked_sub(1)?))
+        .copied()
+        .ok_or_else(|| JCloneError::InvalidChoice(answer.to_owned()))
+}
+
+pub fn run(query: String) -> JCloneResult<()> {
+    let mut matches: Vec<(Score, Candidate)> = candidates()?
+        .into_iter()
+        .filter_map(|candidate| {
+            fuzzy::score(&query, &candidate.name).map(|score| (score, candidate))
+        })
+        .collect();
+
+    // Best match first, then the most recently used, then the shortest
+    matches.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .cmp(a_score)
+            .then(b.used_at.cmp(&a.used_at))
+            .then(a.name.len().cmp(&b.name.len()))
+    });
+
+    let Some((best_score, best)) = matches.first() else {
+        return Err(JCloneError::NoMatchingRepository(query));
+    };
+
+    let tied: Vec<&Candidate> = matches
+        .iter()
+        .take_while(|(score, _)| score == best_score)
+        .take(MAX_CHOICES)
+        .map(|(_, candidate)| candidate)
+        .collect();
+
+    let interactive = io::stdin().is_terminal() && io::stderr().is_terminal();
+
+    let chosen = match tied.len() > 1 && interactive {
+        true => choose(&tied)?,
+        false => best,
+    };
+
+    println!("{}", chosen.dir.display());
+
+    Ok(())
+}
@@ -0,0 +1,291 @@
+use std::fs;
+use std::path::{self, Path, PathBuf};
+
+use crate::commands::{self, list, rm};
+use crate::configuration::Configuration;
+use crate::errors::{JCloneError, JCloneResult};
+use crate::git::Git;
+use crate::index::{self, Index};
+use crate::layout::Layout;
+use crate::user_configuration::UserConfiguration;
+use crate::workspace::{self, Checkout, Filter};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Default)]
+struct Usage {
+    bytes: u64,
+    /// When anything outside `.git` was last modified, in seconds since the Unix epoch.
+    modified_at: u64,
+}
+
+/// A checkout nobody has used since the cutoff.
+struct Stale<'a> {
+    checkout: &'a Checkout,
+    bytes: u64,
+    used_at: u64,
+    /// Why it has to be kept, if it does.
+    kept: Option<String>,
+}
+
+/// Adds up the size of everything under `dir` without following symlinks. Returns `false` as
+/// soon as anything outside `.git` turns out to have been modified since `since`, as fetches
+/// change `.git` without anyone using the checkout.
+fn scan(dir: &Path, since: u64, in_git: bool, usage: &mut Usage) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return true;
+    };
+
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let in_git = in_git || entry.file_name() == ".git";
+
+        usage.bytes += metadata.len();
+
+        if !in_git {
+            let modified_at = index::modified_at(&entry.path());
+
+            if modified_at >= since {
+                return false;
+            }
+
+            usage.modified_at = usage.modified_at.max(modified_at);
+        }
+
+        if metadata.is_dir() && !scan(&entry.path(), since, in_git, usage) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// When git was last used in the checkout at `dir`, from `HEAD` and its reflog, which commits
+/// and checkouts update.
+fn git_used_at(dir: &Path) -> u64 {
+    [".git/HEAD", ".git/logs/HEAD"]
+        .iter()
+        .map(|file| index::modified_at(&dir.join(file)))
+        .max()
+        .unwrap_or_default()
+}
+
+/// The checkout, if it has no commits, checkouts or changes to its files since `since`.
+/// Anything that can't be read counts as recent, so it's kept.
+fn find_stale<'a>(
+    user_config: &UserConfiguration,
+    checkout: &'a Checkout,
+    since: u64,
+) -> Option<Stale<'a>> {
+    let git_used_at = git_used_at(&checkout.dir);
+
+    if git_used_at >= since {
+        return None;
+    }
+
+    let host = checkout.host.to_owned().unwrap_or_default();
+    let config = Configuration::try_for_host(user_config.to_owned(), &host).ok()?;
+    let git = Git::local(&config);
+    let committed_at = git.last_commit_at(&checkout.dir).ok()?.unwrap_or_default();
+
+    if committed_at >= since {
+        return None;
+    }
+
+    let mut usage = Usage::default();
+
+    if !scan(&checkout.dir, since, false, &mut usage) {
+        return None;
+    }
+
+    // Pruning a symlinked checkout only removes the link, which frees nothing and loses nothing
+    let symlink = checkout.dir.is_symlink();
+
+    let kept = match symlink {
+        true => None,
+        false => match rm::check_removable(&git, &checkout.dir) {
+            Ok(()) => None,
+            Err(JCloneError::LocalWork { work, .. }) => Some(work),
+            Err(err) => Some(err.to_string()),
+        },
+    };
+
+    Some(Stale {
+        checkout,
+        bytes: if symlink { 0 } else { usage.bytes },
+        used_at: git_used_at.max(committed_at).max(usage.modified_at),
+        kept,
+    })
+}
+
+/// A size in bytes in binary units, e.g. `1.5 GiB`.
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = "B";
+
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+
+        size /= 1024.0;
+        unit = next;
+    }
+
+    match unit {
+        "B" => format!("{bytes} B"),
+        _ => format!("{size:.1} {unit}"),
+    }
+}
+
+pub fn run(
+    filter: Filter,
+    older_than: u64,
+    archive: Option<PathBuf>,
+    yes: bool,
+) -> JCloneResult<()> {
+    let user_config = UserConfiguration::try_load()?;
+    let layouts = Layout::try_all(&user_config)?;
+    let checkouts = list::matching_checkouts(&user_config, &filter)?;
+    let archive = archive
+        .map(path::absolute)
+        .transpose()
+        .map_err(JCloneError::CurrentDirectory)?;
+
+    let now = index::now();
+    let since = now.saturating_sub(older_than);
+
+    let stale: Vec<Stale> =
+        workspace::map_parallel(&checkouts, workspace::default_jobs(), |checkout| {
+            find_stale(&user_config, checkout, since)
+        })
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let size_width = stale
+        .iter()
+        .map(|stale| human_size(stale.bytes).len())
+        .max()
+        .unwrap_or_default();
+
+    for stale in &stale {
+        let days = now.saturating_sub(stale.used_at) / SECONDS_PER_DAY;
+        let kept = match &stale.kept {
+            Some(work) => format!(", keeping it as it has {work}"),
+            None => String::new(),
+        };
+
+        println!(
+            "{:>size_width$}  {}  last used {days} days ago{kept}",
+            human_size(stale.bytes),
+            stale.checkout.name(),
+        );
+    }
+
+    let prunable: Vec<&Stale> = stale.iter().filter(|stale| stale.kept.is_none()).collect();
+    let total = human_size(prunable.iter().map(|stale| stale.bytes).sum());
+
+    if prunable.is_empty() {
+        println!("✅ Nothing to prune");
+        return Ok(());
+    }
+
+    let count = commands::plural(prunable.len(), "repository", "repositories");
+
+    let action = match archive {
+        Some(_) => "Archive",
+        None => "Remove",
+    };
+    commands::confirm(&format!("{action} {count} ({total})?"), yes)?;
+
+    let stop_dirs: Vec<&Path> = layouts
+        .iter()
+        .map(|layout| layout.base_dir.as_path())
+        .collect();
+    let mut pruned = vec![];
+
+    for stale in &prunable {
+        let dir = &stale.checkout.dir;
+
+        let result = match &archive {
+            Some(archive) => {
+                let to = archive.join(stale.checkout.name());
+
+                match to.symlink_metadata() {
+                    Ok(_) => Err(format!("{} already exists", to.display())),
+                    Err(_) => workspace::move_dir(dir, &to)
+                        .map(|()| workspace::remove_empty_parents(dir, &stop_dirs))
+                        .map_err(|err| err.to_string()),
+                }
+            }
+            None => rm::remove(dir, &stop_dirs).map_err(|err| err.to_string()),
+        };
+
+        match result {
+            Ok(()) => pruned.push(dir.as_path()),
+            Err(err) => println!("❌ Couldn't prune {}: {err}", stale.checkout.name()),
+        }
+    }
+
+    Index::update_default(|index| index.forget(&pruned));
+
+    match prunable.len() - pruned.len() {
+        0 => {
+            println!("🎉 Pruned {count} ({total})");
+            Ok(())
+        }
+        n => Err(JCloneError::PruneFailed(n, prunable.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::bytes(512, "512 B")]
+    #[case::kibibytes(1536, "1.5 KiB")]
+    #[case::gibibytes(3 * 1024 * 1024 * 1024, "3.0 GiB")]
+    fn test_human_size(#[case] bytes: u64, #[case] expected: &str) {
+        assert_eq!(human_size(bytes), expected);
+    }
+
+    #[test]
+    fn test_scan_adds_up_old_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "0123456789").unwrap();
+
+        let mut usage = Usage::default();
+
+        assert!(scan(dir.path(), index::now() + 60, false, &mut usage));
+        assert!(usage.bytes >= 10);
+        assert!(usage.modified_at > 0);
+    }
+
+    #[test]
+    fn test_scan_stops_at_recent_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        assert!(!scan(dir.path(), 0, false, &mut Usage::default()));
+    }
+
+    #[test]
+    fn test_scan_ignores_recent_git_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".git/objects")).unwrap();
+
+        let mut usage = Usage::default();
+
+        assert!(scan(dir.path(), 0, false, &mut usage));
+        assert_eq!(usage.modified_at, 0);
+    }
+}
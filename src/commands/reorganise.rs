@@ -0,0 +1,284 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::path::{self, Path, PathBuf};
+
+use crate::commands;
+use crate::configuration::Configuration;
+use crate::errors::{JCloneError, JCloneResult};
+use crate::git::Git;
+use crate::index::{self, Index, IndexEntry};
+use crate::layout::{self, Destination, Layout};
+use crate::user_configuration::UserConfiguration;
+use crate::workspace;
+
+/// A checkout that isn't where jclone would clone it now.
+pub struct Move {
+    pub from: PathBuf,
+    pub url: String,
+    pub destination: Destination,
+}
+
+/// The checkouts in the index and under the configured base directories that the config in
+/// effect applies to, and any under `dirs`. Checkouts under another directory-scoped config are
+/// left alone, so they aren't moved into the wrong tree.
+fn checkout_dirs(layouts: &[Layout], dirs: &[PathBuf]) -> JCloneResult<Vec<PathBuf>> {
+    let config_paths = UserConfiguration::try_config_paths()?;
+    let mut found = BTreeSet::new();
+
+    let candidates = Index::try_default()?
+        .load()?
+        .into_iter()
+        .map(|entry| entry.dir)
+        .filter(|dir| dir.join(".git").exists())
+        .chain(
+            workspace::find_checkouts(layouts)
+                .into_iter()
+                .map(|checkout| checkout.dir),
+        );
+
+    for dir in candidates {
+        if UserConfiguration::try_config_paths_in(&dir)? == config_paths {
+            found.insert(dir);
+        }
+    }
+
+    for dir in dirs {
+        found.extend(workspace::find_checkout_dirs(dir));
+    }
+
+    Ok(found.into_iter().collect())
+}
+
+/// Why `planned` can't be moved, if it can't.
+pub fn collision(planned: &Move, moves: &[Move]) -> Option<String> {
+    let to = &planned.destination.target_dir;
+
+    if to.starts_with(&planned.from) {
+        return Some(format!("{} is inside it", to.display()));
+    }
+
+    if to.symlink_metadata().is_ok() {
+        return Some(format!("{} already exists", to.display()));
+    }
+
+    if let Some(checkout) = to.ancestors().find(|dir| dir.join(".git").exists()) {
+        return Some(format!(
+            "{} would be inside the checkout at {}",
+            to.display(),
+            checkout.display()
+        ));
+    }
+
+    let sharing = moves
+        .iter()
+        .filter(|other| other.destination.target_dir == *to)
+        .count();
+
+    (sharing > 1).then(|| format!("{sharing} checkouts would move to {}", to.display()))
+}
+
+/// Records the moved checkouts at their new directories, keeping when they were cloned.
+pub fn record_moves(index: &Index, moved: &[&Move]) -> JCloneResult<()> {
+    index.update(|entries| {
+        for planned in moved {
+            let to = &planned.destination.target_dir;
+
+            let cloned_at = entries
+                .iter()
+                .find(|entry| entry.dir == planned.from)
+                .map_or_else(|| index::git_modified_at(to), |entry| entry.cloned_at);
+
+            entries.retain(|entry| entry.dir != planned.from && entry.dir != *to);
+            entries.push(IndexEntry {
+                url: planned.url.to_owned(),
+                repository: planned.destination.repository.to_owned(),
+                dir: to.to_owned(),
+                cloned_at,
+                variant: planned.destination.variant.to_owned(),
+            });
+        }
+    })
+}
+
+pub fn run(yes: bool, dirs: Vec<PathBuf>) -> JCloneResult<()> {
+    let user_config = UserConfiguration::try_load()?;
+    let layouts = Layout::try_all(&user_config)?;
+    let dirs: Vec<PathBuf> = dirs
+        .iter()
+        .map(path::absolute)
+        .collect::<Result<_, _>>()
+        .map_err(JCloneError::CurrentDirectory)?;
+
+    let base_config = Configuration::try_for_host(user_config.to_owned(), &String::new())?;
+    let git = Git::local(&base_config);
+    let mut moves = vec![];
+
+    for from in checkout_dirs(&layouts, &dirs)? {
+        let Some(url) = git.remote_url(&from)? else {
+            println!("⚠️ Skipping {}: it has no origin remote", from.display());
+            continue;
+        };
+
+        match layout::destination(&user_config, &url) {
+            Ok(destination) if destination.target_dir == from => (),
+            Ok(destination) => moves.push(Move {
+                from,
+                url,
+                destination,
+            }),
+            Err(err) => println!("⚠️ Skipping {}: {err}", from.display()),
+        }
+    }
+
+    let mut planned = vec![];
+
+    for candidate in &moves {
+        match collision(candidate, &moves) {
+            Some(reason) => println!("⚠️ Can't move {}: {reason}", candidate.from.display()),
+            None => {
+                println!(
+                    "📂 {} → {}",
+                    candidate.from.display(),
+                    candidate.destination.target_dir.display()
+                );
+                planned.push(candidate);
+            }
+        }
+    }
+
+    if planned.is_empty() {
+        println!("✅ Nothing to move");
+        return Ok(());
+    }
+
+    let count = commands::plural(planned.len(), "repository", "repositories");
+
+    commands::confirm(&format!("Move {count}?"), yes)?;
+
+    let home = PathBuf::from(env::var("HOME")?);
+    let mut stop_dirs: Vec<&Path> = layouts
+        .iter()
+        .map(|layout| layout.base_dir.as_path())
+        .collect();
+    stop_dirs.extend(dirs.iter().map(PathBuf::as_path));
+    stop_dirs.push(&home);
+
+    let mut moved = vec![];
+
+    for planned in &planned {
+        match workspace::move_dir(&planned.from, &planned.destination.target_dir) {
+            Ok(()) => {
+                workspace::remove_empty_parents(&planned.from, &stop_dirs);
+                moved.push(*planned);
+            }
+            Err(err) => println!("❌ Couldn't move {}: {err}", planned.from.display()),
+        }
+    }
+
+    Index::update_default(|index| record_moves(index, &moved));
+
+    match planned.len() - moved.len() {
+        0 => {
+            println!("🎉 Moved {count}");
+            Ok(())
+        }
+        n => Err(JCloneError::MoveFailed(n, planned.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::repository::Repository;
+
+    use super::*;
+
+    fn planned(from: &Path, to: &Path) -> Move {
+        Move {
+            from: from.to_owned(),
+            url: String::from("git@example.com:team/app.git"),
+            destination: Destination {
+                repository: Repository {
+                    host: String::from("example.com"),
+                    path: String::from("team/app"),
+                },
+                variant: None,
+                target_dir: to.to_owned(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_collision_free_move() {
+        let dir = tempfile::tempdir().unwrap();
+        let moves = vec![planned(
+            &dir.path().join("old/app"),
+            &dir.path().join("new/app"),
+        )];
+
+        assert_eq!(collision(&moves[0], &moves), None);
+    }
+
+    #[test]
+    fn test_collision_with_existing_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("new/app")).unwrap();
+        let moves = vec![planned(
+            &dir.path().join("old/app"),
+            &dir.path().join("new/app"),
+        )];
+
+        assert!(
+            collision(&moves[0], &moves)
+                .unwrap()
+                .ends_with("already exists")
+        );
+    }
+
+    #[test]
+    fn test_collision_inside_checkout() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("new/.git")).unwrap();
+        let moves = vec![planned(
+            &dir.path().join("old/app"),
+            &dir.path().join("new/app"),
+        )];
+
+        assert!(
+            collision(&moves[0], &moves)
+                .unwrap()
+                .contains("would be inside the checkout")
+        );
+    }
+
+    #[test]
+    fn test_collision_inside_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let moves = vec![planned(
+            &dir.path().join("app"),
+            &dir.path().join("app/team/app"),
+        )];
+
+        assert!(
+            collision(&moves[0], &moves)
+                .unwrap()
+                .ends_with("is inside it")
+        );
+    }
+
+    #[test]
+    fn test_collision_between_moves() {
+        let dir = tempfile::tempdir().unwrap();
+        let moves = vec![
+            planned(&dir.path().join("a/app"), &dir.path().join("new/app")),
+            planned(&dir.path().join("b/app"), &dir.path().join("new/app")),
+        ];
+
+        assert!(
+            collision(&moves[1], &moves)
+                .unwrap()
+                .starts_with("2 checkouts would move")
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs;
+use std::path::{self, Path, PathBuf};
+
+use crate::configuration::Configuration;
+use crate::errors::{JCloneError, JCloneResult};
+use crate::git::Git;
+use crate::index::Index;
+use crate::layout::{self, Layout};
+use crate::user_configuration::UserConfiguration;
+use crate::workspace;
+
+/// Finds the checkout `query` refers to: its directory, its name as `jclone list` shows it
+/// (with or without the host), or the URL it was cloned from.
+fn locate(
+    user_config: &UserConfiguration,
+    layouts: &[Layout],
+    indexed: &[PathBuf],
+    query: &str,
+) -> JCloneResult<PathBuf> {
+    let as_dir = Path::new(query);
+
+    if as_dir.join(".git").exists() {
+        let dir = path::absolute(as_dir).map_err(JCloneError::CurrentDirectory)?;
+
+        // A dotfiles repository can be a base directory, and removing it would take every
+        // checkout under it too
+        if layouts.iter().any(|layout| layout.base_dir == dir) {
+            return Err(JCloneError::RemoveBaseDirectory(dir));
+        }
+
+        let managed = indexed.contains(&dir)
+            || layouts
+                .iter()
+                .any(|layout| dir.starts_with(&layout.base_dir));
+
+        return match managed {
+            true => Ok(dir),
+            false => Err(JCloneError::NotManaged(dir)),
+        };
+    }
+
+    let named: Vec<PathBuf> = workspace::find_checkouts(layouts)
+        .into_iter()
+        .filter(|checkout| checkout.name() == query || checkout.path == query)
+        .map(|checkout| checkout.dir)
+        .collect();
+
+    match named.as_slice() {
+        [dir] => return Ok(dir.to_owned()),
+        [] => (),
+        _ => return Err(JCloneError::AmbiguousRepository(query.to_owned())),
+    }
+
+    match layout::destination(user_config, query) {
+        Ok(destination) if destination.target_dir.join(".git").exists() => {
+            Ok(destination.target_dir)
+        }
+        _ => Err(JCloneError::NoMatchingRepository(query.to_owned())),
+    }
+}
+
+/// Refuses to go on if the checkout at `dir` has work that would be lost by removing it.
+pub fn check_removable(git: &Git, dir: &Path) -> JCloneResult<()> {
+    let local_work = git.status(dir)?.local_work();
+
+    match local_work.is_empty() {
+        true => Ok(()),
+        false => Err(JCloneError::LocalWork {
+            dir: dir.to_owned(),
+            work: local_work.join(", "),
+        }),
+    }
+}
+
+/// Removes the checkout at `dir`, and any of its parents inside `stop_dirs` left empty. A
+/// symlinked checkout only loses the link.
+pub fn remove(dir: &Path, stop_dirs: &[&Path]) -> JCloneResult<()> {
+    let removed = match dir.is_symlink() {
+        true => fs::remove_file(dir),
+        false => fs::remove_dir_all(dir),
+    };
+
+    removed.map_err(|source| JCloneError::Remove {
+        dir: dir.to_owned(),
+        source,
+    })?;
+
+    workspace::remove_empty_parents(dir, stop_dirs);
+
+    Ok(())
+}
+
+pub fn run(query: String, force: bool) -> JCloneResult<()> {
+    let user_config = UserConfiguration::try_load()?;
+    let layouts = Layout::try_all(&user_config)?;
+    let indexed: Vec<PathBuf> = Index::try_default()?
+        .load()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| entry.dir)
+        .collect();
+
+    let dir = locate(&user_config, &layouts, &indexed, &query)?;
+
+    // Removing a symlinked checkout only removes the link, so nothing in it can be lost
+    if !force && !dir.is_symlink() {
+        let host = workspace::find_checkouts(&layouts)
+            .into_iter()
+            .find(|checkout| checkout.dir == dir)
+            .and_then(|checkout| checkout.host);
+        let config =
+            Configuration::try_for_host(user_config.to_owned(), &host.unwrap_or_default())?;
+
+        check_removable(&Git::local(&config), &dir)?;
+    }
+
+    let stop_dirs: Vec<&Path> = layouts
+        .iter()
+        .map(|layout| layout.base_dir.as_path())
+        .collect();
+
+    remove(&dir, &stop_dirs)?;
+    Index::update_default(|index| index.forget(&[&dir]));
+
+    println!("✅ Removed {}", dir.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix;
+
+    use super::*;
+
+    #[test]
+    fn test_locate_refuses_base_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+
+        let user_config = UserConfiguration {
+            base_dir: Some(dir.path().to_string_lossy().into_owned()),
+            ..UserConfiguration::default()
+        };
+        let layouts = Layout::try_all(&user_config).unwrap();
+        let query = dir.path().to_string_lossy();
+
+        assert!(matches!(
+            locate(&user_config, &layouts, &[], &query),
+            Err(JCloneError::RemoveBaseDirectory(_))
+        ));
+    }
+
+    #[test]
+    fn test_remove_prunes_empty_parents() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        let checkout = src.join("example.com/team/app");
+        fs::create_dir_all(checkout.join(".git")).unwrap();
+        fs::create_dir_all(src.join("example.com/other")).unwrap();
+
+        remove(&checkout, &[&src]).unwrap();
+
+        assert!(!src.join("example.com/team").exists());
+        assert!(src.join("example.com/other").exists());
+    }
+
+    #[test]
+    fn test_remove_symlink_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("projects/app");
+        let link = dir.path().join("src/example.com/team/app");
+        fs::create_dir_all(original.join(".git")).unwrap();
+        fs::create_dir_all(link.parent().unwrap()).unwrap();
+        unix::fs::symlink(&original, &link).unwrap();
+
+        remove(&link, &[&dir.path().join("src")]).unwrap();
+
+        assert!(!link.is_symlink());
+        assert!(original.join(".git").is_dir());
+    }
+}
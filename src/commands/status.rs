@@ -0,0 +1,101 @@
+use serde::Serialize;
+
+use crate::commands::{self, list};
+use crate::configuration::Configuration;
+use crate::errors::{JCloneError, JCloneResult};
+use crate::git::{CheckoutStatus, Git};
+use crate::user_configuration::UserConfiguration;
+use crate::workspace::{self, Checkout, Filter};
+
+/// A checkout's status, or why it couldn't be read.
+#[derive(Serialize)]
+struct Report<'a> {
+    #[serde(flatten)]
+    checkout: &'a Checkout,
+    status: Option<CheckoutStatus>,
+    error: Option<String>,
+    needs_attention: bool,
+}
+
+fn report<'a>(user_config: &UserConfiguration, checkout: &'a Checkout) -> Report<'a> {
+    let host = checkout.host.to_owned().unwrap_or_default();
+    let status = Configuration::try_for_host(user_config.to_owned(), &host)
+        .and_then(|config| Git::local(&config).status(&checkout.dir));
+
+    match status {
+        Ok(status) => Report {
+            checkout,
+            needs_attention: status.needs_attention(),
+            status: Some(status),
+            error: None,
+        },
+        Err(err) => Report {
+            checkout,
+            status: None,
+            error: Some(err.to_string()),
+            needs_attention: true,
+        },
+    }
+}
+
+/// Prints the checkouts with anything to report, aligned by name.
+fn print_table(reports: &[Report]) {
+    let rows: Vec<(String, String, String)> = reports
+        .iter()
+        .filter_map(|report| {
+            let (branch, notes) = match (&report.status, &report.error) {
+                (Some(status), _) => (
+                    status.branch.as_deref().unwrap_or("-").to_owned(),
+                    status.notes().join(", "),
+                ),
+                (None, error) => (String::from("-"), error.to_owned().unwrap_or_default()),
+            };
+
+            (!notes.is_empty()).then(|| (report.checkout.name(), branch, notes))
+        })
+        .collect();
+
+    let name_width = rows.iter().map(|(name, _, _)| name.len()).max();
+    let branch_width = rows.iter().map(|(_, branch, _)| branch.len()).max();
+
+    for (name, branch, notes) in &rows {
+        println!(
+            "{name:name_width$}  {branch:branch_width$}  {notes}",
+            name_width = name_width.unwrap_or_default(),
+            branch_width = branch_width.unwrap_or_default(),
+        );
+    }
+}
+
+pub fn run(filter: Filter, json: bool) -> JCloneResult<()> {
+    let user_config = UserConfiguration::try_load()?;
+    let checkouts = list::matching_checkouts(&user_config, &filter)?;
+
+    let reports = workspace::map_parallel(&checkouts, workspace::default_jobs(), |checkout| {
+        report(&user_config, checkout)
+    });
+
+    match json {
+        true => println!(
+            "{}",
+            serde_json::to_string_pretty(&reports).map_err(JCloneError::JsonOutput)?
+        ),
+        false => print_table(&reports),
+    }
+
+    match reports
+        .iter()
+        .filter(|report| report.needs_attention)
+        .count()
+    {
+        0 => {
+            let count = commands::plural(reports.len(), "repository", "repositories");
+
+            if !json {
+                println!("✅ Nothing needs attention in {count}");
+            }
+            Ok(())
+        }
+        n => Err(JCloneError::NeedsAttention(n, reports.len())),
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+
+use crate::commands::list;
+use crate::configuration::Configuration;
+use crate::errors::{JCloneError, JCloneResult};
+use crate::git::Git;
+use crate::user_configuration::UserConfiguration;
+use crate::workspace::{self, Checkout, Filter};
+
+/// What happened to one checkout.
+enum Outcome {
+    Fetched,
+    UpToDate,
+    /// Fast-forwarded by this many commits.
+    Pulled(usize),
+    Skipped(&'static str),
+    Failed(String),
+}
+
+impl Outcome {
+    /// How it's counted in the summary.
+    fn kind(&self) -> &'static str {
+        match self {
+            Outcome::Fetched => "fetched",
+            Outcome::UpToDate => "up to date",
+            Outcome::Pulled(_) => "pulled",
+            Outcome::Skipped(_) => "skipped",
+            Outcome::Failed(_) => "failed",
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Fetched => write!(f, "fetched"),
+            Outcome::UpToDate => write!(f, "up to date"),
+            Outcome::Pulled(1) => write!(f, "pulled 1 commit"),
+            Outcome::Pulled(n) => write!(f, "pulled {n} commits"),
+            Outcome::Skipped(reason) => write!(f, "skipped, {reason}"),
+            Outcome::Failed(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+/// Fetches the checkout, then fast-forwards it if `pull` is set and that can't lose any work.
+fn sync(git: &Git, checkout: &Checkout, pull: bool) -> JCloneResult<Outcome> {
+    git.fetch(&checkout.dir)?;
+
+    if !pull {
+        return Ok(Outcome::Fetched);
+    }
+
+    let status = git.status(&checkout.dir)?;
+
+    let outcome = if status.branch.is_none() {
+        Outcome::Skipped("detached")
+    } else if status.upstream.is_none() {
+        Outcome::Skipped("no upstream")
+    } else if status.behind == 0 {
+        Outcome::UpToDate
+    } else if status.ahead > 0 {
+        Outcome::Skipped("diverged from upstream")
+    } else if status.is_dirty() {
+        Outcome::Skipped("uncommitted changes")
+    } else {
+        git.fast_forward(&checkout.dir)?;
+        Outcome::Pulled(status.behind)
+    };
+
+    Ok(outcome)
+}
+
+pub fn run(filter: Filter, pull: bool, jobs: Option<usize>) -> JCloneResult<()> {
+    let user_config = UserConfiguration::try_load()?;
+    let checkouts = list::matching_checkouts(&user_config, &filter)?;
+
+    let jobs = jobs.unwrap_or_else(workspace::default_jobs);
+    let outcomes = workspace::map_parallel(&checkouts, jobs, |checkout| {
+        let host = checkout.host.to_owned().unwrap_or_default();
+        let outcome = Configuration::try_for_host(user_config.to_owned(), &host)
+            .and_then(|config| sync(&Git::local(&config), checkout, pull))
+            .unwrap_or_else(|err| Outcome::Failed(err.to_string()));
+
+        // Printed as each one finishes, so there's progress to watch
+        let emoji = match outcome {
+            Outcome::Skipped(_) => "⚠️",
+            Outcome::Failed(_) => "❌",
+            _ => "✅",
+        };
+        println!("{emoji} {}: {outcome}", checkout.name());
+
+        outcome
+    });
+
+    let summary: Vec<String> = ["fetched", "pulled", "up to date", "skipped", "failed"]
+        .into_iter()
+        .map(|kind| (outcomes.iter().filter(|o| o.kind() == kind).count(), kind))
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, kind)| format!("{n} {kind}"))
+        .collect();
+
+    if !summary.is_empty() {
+        println!("📝 {}", summary.join(", "));
+    }
+
+    match outcomes.iter().filter(|o| o.kind() == "failed").count() {
+        0 => {
+            println!("🎉 Done!");
+            Ok(())
+        }
+        n => Err(JCloneError::SyncFailed(n, outcomes.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::pulled_one(Outcome::Pulled(1), "pulled 1 commit")]
+    #[case::pulled_many(Outcome::Pulled(3), "pulled 3 commits")]
+    #[case::skipped(Outcome::Skipped("detached"), "skipped, detached")]
+    fn test_outcome_display(#[case] outcome: Outcome, #[case] expected: &str) {
+        assert_eq!(outcome.to_string(), expected);
+    }
+}
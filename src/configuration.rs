@@ -1,8 +1,10 @@
-use std::{env, path::PathBuf};
+use std::{collections::BTreeMap, env, path::PathBuf};
 
 use crate::{
     errors::{JCloneError, JCloneResult},
-    user_configuration::{OutputStyle, UserConfiguration},
+    hooks::shell_quote,
+    repository::DEFAULT_LOCAL_HOST,
+    user_configuration::{Forge, OutputStyle, Protocol, UserConfiguration},
 };
 
 #[derive(Debug, PartialEq)]
@@ -12,9 +14,27 @@ pub struct Configuration {
     pub use_full_path: bool,
     pub output_style: OutputStyle,
     pub git_executable: String,
+    pub prefer_protocol: Option<Protocol>,
+    pub ssh_user: String,
+    pub user_name: Option<String>,
+    pub user_email: Option<String>,
+    pub signing_key: Option<String>,
+    pub post_clone: Vec<String>,
+    pub allowed_hosts: Vec<String>,
+    pub denied_hosts: Vec<String>,
+    pub pre_clone: Option<String>,
+    pub ssh_key: Option<String>,
+    pub ssh_options: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub persist_ssh_command: bool,
+    pub resolve_ssh_aliases: bool,
+    pub checkout_linked_ref: bool,
+    pub forge: Forge,
+    pub local_host: String,
+    pub local_path_prefixes: Vec<String>,
 }
 
-fn default_base_dir(home: String) -> JCloneResult<String> {
+pub fn default_base_dir(home: String) -> JCloneResult<String> {
     Ok(PathBuf::from(home)
         .join("src")
         .to_str()
@@ -22,13 +42,30 @@ fn default_base_dir(home: String) -> JCloneResult<String> {
         .to_owned())
 }
 
+fn expand_home(path: String, home: &str) -> String {
+    match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(home)
+            .join(rest)
+            .to_string_lossy()
+            .into_owned(),
+        None => path,
+    }
+}
+
 impl Configuration {
-    fn from_user_configuration(
+    pub fn from_user_configuration(
         user_config: UserConfiguration,
         host: &String,
         home: String,
     ) -> JCloneResult<Self> {
         let host_variant = user_config.variant_matching_host(host);
+        let ssh_key = host_variant.ssh_key.map(|key| expand_home(key, &home));
+        let local_path_prefixes = user_config
+            .local_path_prefixes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|prefix| expand_home(prefix, &home))
+            .collect();
 
         let base_dir = match host_variant.base_dir.or(user_config.base_dir) {
             Some(dir) => dir,
@@ -53,11 +90,90 @@ impl Configuration {
                 .git_executable
                 .or(user_config.git_executable)
                 .unwrap_or(String::from("git")),
+            prefer_protocol: host_variant.prefer_protocol.or(user_config.prefer_protocol),
+            ssh_user: host_variant
+                .ssh_user
+                .or(user_config.ssh_user)
+                .unwrap_or(String::from("git")),
+            user_name: host_variant.user_name,
+            user_email: host_variant.user_email,
+            signing_key: host_variant.signing_key,
+            post_clone: host_variant
+                .post_clone
+                .or(user_config.post_clone)
+                .unwrap_or_default(),
+            allowed_hosts: user_config.allowed_hosts.unwrap_or_default(),
+            denied_hosts: user_config.denied_hosts.unwrap_or_default(),
+            pre_clone: user_config.pre_clone,
+            ssh_key,
+            ssh_options: host_variant.ssh_options.unwrap_or_default(),
+            env: host_variant.env.unwrap_or_default(),
+            persist_ssh_command: host_variant.persist_ssh_command.unwrap_or(false),
+            resolve_ssh_aliases: user_config.resolve_ssh_aliases.unwrap_or(false),
+            checkout_linked_ref: host_variant
+                .checkout_linked_ref
+                .or(user_config.checkout_linked_ref)
+                .unwrap_or(false),
+            forge: host_variant.forge.unwrap_or_default(),
+            local_host: user_config
+                .local_host
+                .unwrap_or(String::from(DEFAULT_LOCAL_HOST)),
+            local_path_prefixes,
         })
     }
 
-    pub fn try_load(host: &String) -> JCloneResult<Self> {
-        let user_config = UserConfiguration::try_load()?;
+    /// The command git should use for SSH, if `ssh_key` or `ssh_options` are set.
+    pub fn ssh_command(&self) -> Option<String> {
+        if self.ssh_key.is_none() && self.ssh_options.is_empty() {
+            return None;
+        }
+
+        let mut command = String::from("ssh");
+
+        if let Some(key) = &self.ssh_key {
+            command.push_str(&format!(" -i {} -o IdentitiesOnly=yes", shell_quote(key)));
+        }
+
+        for option in &self.ssh_options {
+            command.push_str(&format!(" -o {}", shell_quote(option)));
+        }
+
+        Some(command)
+    }
+
+    /// Environment for every git command, with any `env` values overriding `GIT_SSH_COMMAND`.
+    pub fn git_env(&self) -> BTreeMap<String, String> {
+        let mut git_env = BTreeMap::new();
+
+        if let Some(ssh_command) = self.ssh_command() {
+            git_env.insert(String::from("GIT_SSH_COMMAND"), ssh_command);
+        }
+
+        git_env.extend(self.env.to_owned());
+        git_env
+    }
+
+    /// Settings written to the new repository's local git config after cloning.
+    pub fn local_git_config(&self) -> Vec<(&'static str, String)> {
+        let ssh_command = match self.persist_ssh_command {
+            true => self.ssh_command(),
+            false => None,
+        };
+
+        [
+            ("user.name", self.user_name.to_owned()),
+            ("user.email", self.user_email.to_owned()),
+            ("user.signingKey", self.signing_key.to_owned()),
+            ("core.sshCommand", ssh_command),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| (key, value)))
+        .collect()
+    }
+
+    /// Resolves the configuration for `host`. An empty host matches no variants, leaving just
+    /// the top-level settings.
+    pub fn try_for_host(user_config: UserConfiguration, host: &String) -> JCloneResult<Self> {
         let home_str = env::var("HOME").map_err(JCloneError::Environment)?;
 
         Self::from_user_configuration(user_config, host, home_str)
@@ -77,11 +193,23 @@ mod tests {
     #[fixture]
     fn base_user_config() -> UserConfiguration {
         UserConfiguration {
+            version: None,
             base_dir: Some(String::from("/some/other/directory")),
+            relative_base_dir: None,
             use_host_dir: Some(false),
             use_full_path: Some(false),
             output_style: Some(OutputStyle::Quiet),
             git_executable: Some(String::from("/home/ferris/bin/git")),
+            prefer_protocol: None,
+            ssh_user: None,
+            post_clone: Some(vec![String::from("make setup")]),
+            allowed_hosts: None,
+            denied_hosts: None,
+            pre_clone: None,
+            resolve_ssh_aliases: None,
+            checkout_linked_ref: None,
+            local_host: None,
+            local_path_prefixes: None,
             variants: Default::default(),
         }
     }
@@ -102,6 +230,21 @@ mod tests {
                     use_full_path: Some(true),
                     output_style: Some(OutputStyle::GitOnly),
                     git_executable: Some(String::from("bin/git-example-com")),
+                    prefer_protocol: None,
+                    ssh_user: None,
+                    user_name: Some(String::from("Ferris")),
+                    user_email: Some(String::from("ferris@example.com")),
+                    signing_key: Some(String::from("ABCD1234")),
+                    post_clone: Some(vec![String::from("pre-commit install")]),
+                    ssh_key: Some(String::from("~/.ssh/id_example")),
+                    ssh_options: Some(vec![String::from("Port=2222")]),
+                    env: Some(BTreeMap::from([(
+                        String::from("GIT_TRACE"),
+                        String::from("1"),
+                    )])),
+                    persist_ssh_command: Some(true),
+                    checkout_linked_ref: None,
+                    forge: None,
                 },
             ],
             ..base_user_config()
@@ -122,6 +265,24 @@ mod tests {
             use_full_path: true,
             output_style: OutputStyle::Default,
             git_executable: String::from("git"),
+            prefer_protocol: None,
+            ssh_user: String::from("git"),
+            user_name: None,
+            user_email: None,
+            signing_key: None,
+            post_clone: vec![],
+            allowed_hosts: vec![],
+            denied_hosts: vec![],
+            pre_clone: None,
+            ssh_key: None,
+            ssh_options: vec![],
+            env: BTreeMap::new(),
+            persist_ssh_command: false,
+            resolve_ssh_aliases: false,
+            checkout_linked_ref: false,
+            forge: Forge::Auto,
+            local_host: String::from("local"),
+            local_path_prefixes: vec![],
         };
 
         assert_eq!(actual, expected);
@@ -139,6 +300,24 @@ mod tests {
             use_full_path: false,
             output_style: OutputStyle::Quiet,
             git_executable: String::from("/home/ferris/bin/git"),
+            prefer_protocol: None,
+            ssh_user: String::from("git"),
+            user_name: None,
+            user_email: None,
+            signing_key: None,
+            post_clone: vec![String::from("make setup")],
+            allowed_hosts: vec![],
+            denied_hosts: vec![],
+            pre_clone: None,
+            ssh_key: None,
+            ssh_options: vec![],
+            env: BTreeMap::new(),
+            persist_ssh_command: false,
+            resolve_ssh_aliases: false,
+            checkout_linked_ref: false,
+            forge: Forge::Auto,
+            local_host: String::from("local"),
+            local_path_prefixes: vec![],
         };
 
         assert_eq!(actual, expected);
@@ -157,6 +336,24 @@ mod tests {
             use_full_path: false,
             output_style: OutputStyle::Quiet,
             git_executable: String::from("/home/ferris/bin/git"),
+            prefer_protocol: None,
+            ssh_user: String::from("git"),
+            user_name: None,
+            user_email: None,
+            signing_key: None,
+            post_clone: vec![String::from("make setup")],
+            allowed_hosts: vec![],
+            denied_hosts: vec![],
+            pre_clone: None,
+            ssh_key: None,
+            ssh_options: vec![],
+            env: BTreeMap::new(),
+            persist_ssh_command: false,
+            resolve_ssh_aliases: false,
+            checkout_linked_ref: false,
+            forge: Forge::Auto,
+            local_host: String::from("local"),
+            local_path_prefixes: vec![],
         };
 
         assert_eq!(actual, expected);
@@ -175,6 +372,24 @@ mod tests {
             use_full_path: true,
             output_style: OutputStyle::GitOnly,
             git_executable: String::from("bin/git-example-com"),
+            prefer_protocol: None,
+            ssh_user: String::from("git"),
+            user_name: Some(String::from("Ferris")),
+            user_email: Some(String::from("ferris@example.com")),
+            signing_key: Some(String::from("ABCD1234")),
+            post_clone: vec![String::from("pre-commit install")],
+            allowed_hosts: vec![],
+            denied_hosts: vec![],
+            pre_clone: None,
+            ssh_key: Some(String::from("/some/directory/.ssh/id_example")),
+            ssh_options: vec![String::from("Port=2222")],
+            env: BTreeMap::from([(String::from("GIT_TRACE"), String::from("1"))]),
+            persist_ssh_command: true,
+            resolve_ssh_aliases: false,
+            checkout_linked_ref: false,
+            forge: Forge::Auto,
+            local_host: String::from("local"),
+            local_path_prefixes: vec![],
         };
 
         assert_eq!(actual, expected);
@@ -200,8 +415,98 @@ mod tests {
             use_full_path: true,
             output_style: OutputStyle::Quiet,
             git_executable: String::from("/home/ferris/bin/git"),
+            prefer_protocol: None,
+            ssh_user: String::from("git"),
+            user_name: None,
+            user_email: None,
+            signing_key: None,
+            post_clone: vec![String::from("make setup")],
+            allowed_hosts: vec![],
+            denied_hosts: vec![],
+            pre_clone: None,
+            ssh_key: None,
+            ssh_options: vec![],
+            env: BTreeMap::new(),
+            persist_ssh_command: false,
+            resolve_ssh_aliases: false,
+            checkout_linked_ref: false,
+            forge: Forge::Auto,
+            local_host: String::from("local"),
+            local_path_prefixes: vec![],
         };
 
         assert_eq!(actual, expected);
     }
+
+    #[rstest]
+    fn test_local_git_config_includes_only_set_values(complete_user_config: UserConfiguration) {
+        let partial_user_config = UserConfiguration {
+            variants: vec![UserHostConfiguration {
+                host: String::from("example.com"),
+                user_email: Some(String::from("ferris@example.com")),
+                ..UserHostConfiguration::default()
+            }],
+            ..complete_user_config
+        };
+
+        let host = String::from("example.com");
+        let home = String::from("/some/directory");
+        let config =
+            Configuration::from_user_configuration(partial_user_config, &host, home).unwrap();
+
+        assert_eq!(
+            config.local_git_config(),
+            vec![("user.email", String::from("ferris@example.com"))]
+        );
+    }
+
+    #[rstest]
+    fn test_git_env_includes_ssh_command(complete_user_config: UserConfiguration) {
+        let host = String::from("example.com");
+        let home = String::from("/some/directory");
+        let config =
+            Configuration::from_user_configuration(complete_user_config, &host, home).unwrap();
+
+        let ssh_command =
+            "ssh -i '/some/directory/.ssh/id_example' -o IdentitiesOnly=yes -o 'Port=2222'";
+
+        assert_eq!(
+            config.git_env(),
+            BTreeMap::from([
+                (String::from("GIT_SSH_COMMAND"), String::from(ssh_command)),
+                (String::from("GIT_TRACE"), String::from("1")),
+            ])
+        );
+        assert!(
+            config
+                .local_git_config()
+                .contains(&("core.sshCommand", String::from(ssh_command)))
+        );
+    }
+
+    #[test]
+    fn test_git_env_lets_env_override_ssh_command() {
+        let user_config = UserConfiguration {
+            variants: vec![UserHostConfiguration {
+                host: String::from("example.com"),
+                ssh_options: Some(vec![String::from("Port=2222")]),
+                env: Some(BTreeMap::from([(
+                    String::from("GIT_SSH_COMMAND"),
+                    String::from("my-ssh"),
+                )])),
+                ..UserHostConfiguration::default()
+            }],
+            ..UserConfiguration::default()
+        };
+
+        let host = String::from("example.com");
+        let home = String::from("/some/directory");
+        let config = Configuration::from_user_configuration(user_config, &host, home).unwrap();
+
+        assert_eq!(
+            config.git_env(),
+            BTreeMap::from([(String::from("GIT_SSH_COMMAND"), String::from("my-ssh"))])
+        );
+        assert!(config.local_git_config().is_empty());
+    }
 }
@@ -1,20 +1,52 @@
 use std::{env::VarError, io, path::PathBuf};
 use thiserror::Error;
 
+use crate::commands::plural;
+
 #[derive(Error, Debug)]
 pub enum JCloneError {
     #[error("Error: {0}")]
     Generic(&'static str),
 
+    #[error("Error: {0} (see `jclone --help`)")]
+    Usage(String),
+
     #[error("Error: {0}")]
     Environment(#[from] VarError),
 
+    #[error("Couldn't read current directory: {0}")]
+    CurrentDirectory(#[source] io::Error),
+
     #[error("Couldn't load configuration from {0}: {1}")]
     ConfigurationFileLoad(PathBuf, #[source] io::Error),
 
     #[error("Couldn't parse configuration from: {0}:\n{1}")]
     ConfigurationParse(PathBuf, toml::de::Error),
 
+    #[error("Couldn't parse configuration from: {0}:\n{1}")]
+    ConfigurationDocument(PathBuf, toml_edit::TomlError),
+
+    #[error(
+        "Configuration in {path} is version {version}, but this version of jclone only reads up to version {latest}"
+    )]
+    ConfigurationVersion {
+        path: PathBuf,
+        version: i64,
+        latest: i64,
+    },
+
+    #[error("Found {0} problem(s) in configuration")]
+    ConfigurationCheck(usize),
+
+    #[error("Configuration file already exists: {0}")]
+    ConfigurationExists(PathBuf),
+
+    #[error("Couldn't write configuration to {0}: {1}")]
+    ConfigurationFileWrite(PathBuf, #[source] io::Error),
+
+    #[error("Couldn't load SSH configuration from {0}: {1}")]
+    SshConfigLoad(PathBuf, #[source] io::Error),
+
     #[error("{executable} {command}: {source}")]
     GitSystem {
         executable: String,
@@ -28,8 +60,90 @@ pub enum JCloneError {
         message: &'static str,
     },
 
+    #[error(
+        "{0} has commits that aren't in the pull request, so it wasn't updated (rename or delete it to fetch the pull request again)"
+    )]
+    PullRequestBranchDiverged(String),
+
+    #[error("Cloning from {host} is refused by denied_hosts pattern {pattern}")]
+    HostDenied { host: String, pattern: String },
+
+    #[error("Cloning from {0} is refused because it doesn't match any allowed_hosts pattern")]
+    HostNotAllowed(String),
+
+    #[error("pre_clone check `{command}` refused the clone ({reason})")]
+    PreCloneVeto { command: String, reason: String },
+
+    #[error("post_clone hook `{command}` failed ({reason}), the clone in {} has been kept", path.display())]
+    PostCloneHook {
+        command: String,
+        path: PathBuf,
+        reason: String,
+    },
+
     #[error("Couldn't parse repository: {0}")]
     RepositoryParse(&'static str),
+
+    #[error("Couldn't read the index at {0}: {1}")]
+    IndexLoad(PathBuf, #[source] io::Error),
+
+    #[error("Couldn't parse the index at {0}: {1}")]
+    IndexParse(PathBuf, #[source] serde_json::Error),
+
+    #[error("Couldn't write the index at {0}: {1}")]
+    IndexWrite(PathBuf, #[source] io::Error),
+
+    #[error("No repository matches {0}")]
+    NoMatchingRepository(String),
+
+    #[error("Not one of the choices: {0}")]
+    InvalidChoice(String),
+
+    #[error("{0} of {total} need attention", total = plural(*.1, "repository", "repositories"))]
+    NeedsAttention(usize, usize),
+
+    #[error("{0} of {total} couldn't be synced", total = plural(*.1, "repository", "repositories"))]
+    SyncFailed(usize, usize),
+
+    #[error("The command failed in {0} of {total}", total = plural(*.1, "repository", "repositories"))]
+    ExecFailed(usize, usize),
+
+    #[error("Nothing was changed (pass --yes to go ahead without being asked)")]
+    NotConfirmed,
+
+    #[error("{0} of {total} couldn't be moved", total = plural(*.1, "repository", "repositories"))]
+    MoveFailed(usize, usize),
+
+    #[error("Couldn't adopt {} as {}: {source}", from.display(), to.display())]
+    AdoptMove {
+        from: PathBuf,
+        to: PathBuf,
+        source: io::Error,
+    },
+
+    #[error("{0} of {total} couldn't be adopted", total = plural(*.1, "repository", "repositories"))]
+    AdoptFailed(usize, usize),
+
+    #[error("{} isn't in a base directory or the index", .0.display())]
+    NotManaged(PathBuf),
+
+    #[error("{} is a base directory, so removing it would remove every checkout in it", .0.display())]
+    RemoveBaseDirectory(PathBuf),
+
+    #[error("{0} matches more than one repository, use its full name or directory")]
+    AmbiguousRepository(String),
+
+    #[error("{} has {work} (use --force to remove it anyway)", dir.display())]
+    LocalWork { dir: PathBuf, work: String },
+
+    #[error("Couldn't remove {}: {source}", dir.display())]
+    Remove { dir: PathBuf, source: io::Error },
+
+    #[error("{0} of {total} couldn't be pruned", total = plural(*.1, "repository", "repositories"))]
+    PruneFailed(usize, usize),
+
+    #[error("Couldn't write JSON output: {0}")]
+    JsonOutput(#[source] serde_json::Error),
 }
 
 pub type JCloneResult<T> = Result<T, JCloneError>;
@@ -0,0 +1,351 @@
+use crate::repository::Repository;
+use crate::user_configuration::Forge;
+
+/// Something a forge web URL points to inside a repository.
+#[derive(Debug, PartialEq)]
+pub enum Linked {
+    /// A branch, tag or commit, possibly followed by a path to a file or directory. Ref names
+    /// can contain slashes, so every remaining component is kept to match against the clone's
+    /// refs.
+    Ref(Vec<String>),
+    /// A pull or merge request, and the ref the forge publishes its head under.
+    PullRequest { number: u64, head_ref: String },
+}
+
+/// A forge web URL, split into the repository and what it points to inside it.
+#[derive(Debug, PartialEq)]
+pub struct WebLink {
+    pub repo_url: String,
+    pub linked: Option<Linked>,
+}
+
+/// Pages that belong to a repository but don't point at anything we can check out.
+const PAGES: [&str; 21] = [
+    "actions",
+    "activity",
+    "branches",
+    "compare",
+    "discussions",
+    "downloads",
+    "graphs",
+    "issues",
+    "labels",
+    "merge_requests",
+    "milestones",
+    "network",
+    "packages",
+    "pipelines",
+    "pull-requests",
+    "pulls",
+    "refs",
+    "releases",
+    "security",
+    "settings",
+    "wiki",
+];
+
+/// Hosts known to lay out web pages as `owner/name/page`, rather than allowing nested groups
+/// like GitLab, where anything after `owner/name` could be part of a repository's path.
+const OWNER_NAME_HOSTS: [&str; 5] = [
+    "bitbucket.org",
+    "codeberg.org",
+    "git.sr.ht",
+    "gitea.com",
+    "github.com",
+];
+
+fn linked_ref(components: &[&str]) -> Option<Linked> {
+    match components.is_empty() {
+        true => None,
+        false => Some(Linked::Ref(
+            components.iter().map(|c| c.to_string()).collect(),
+        )),
+    }
+}
+
+fn pull_request(number: &str, head_ref: impl Fn(u64) -> String) -> Option<Linked> {
+    let number = number.parse().ok()?;
+
+    Some(Linked::PullRequest {
+        number,
+        head_ref: head_ref(number),
+    })
+}
+
+/// What a GitLab page after `/-/` points to.
+fn gitlab_linked(page: &[&str]) -> Option<Linked> {
+    match page {
+        [
+            "tree" | "blob" | "raw" | "blame" | "commits" | "commit",
+            rest @ ..,
+        ] => linked_ref(rest),
+        ["merge_requests", number, ..] => {
+            pull_request(number, |n| format!("refs/merge-requests/{n}/head"))
+        }
+        _ => None,
+    }
+}
+
+/// What a page after `owner/name` points to, for GitHub, Gitea/Forgejo, Bitbucket and
+/// SourceHut. The outer `None` means the URL isn't a web page we recognise.
+fn owner_name_linked(page: &[&str]) -> Option<Option<Linked>> {
+    match page {
+        // Gitea and Forgejo say what kind of ref it is
+        [
+            "src" | "raw" | "commits" | "blame",
+            "branch" | "tag" | "commit",
+            rest @ ..,
+        ] => Some(linked_ref(rest)),
+        // GitHub, Gitea and Forgejo all publish pull requests under refs/pull/. Bitbucket doesn't
+        // publish them at all.
+        ["pull" | "pulls", number, ..] => {
+            Some(pull_request(number, |n| format!("refs/pull/{n}/head")))
+        }
+        // SourceHut puts the path to a file after /item/
+        ["tree", rest @ ..] if rest.contains(&"item") => {
+            let item = rest.iter().position(|c| *c == "item").unwrap_or_default();
+            Some(linked_ref(&rest[..item]))
+        }
+        [
+            "tree" | "blob" | "blame" | "raw" | "commit" | "commits" | "log" | "src" | "branch",
+            rest @ ..,
+        ] => Some(linked_ref(rest)),
+        [page, ..] if PAGES.contains(page) => Some(None),
+        _ => None,
+    }
+}
+
+fn is_owner_name_host(authority: &str) -> bool {
+    let host_and_port = authority.rsplit('@').next().unwrap_or(authority);
+    let host = host_and_port.split(':').next().unwrap_or(host_and_port);
+
+    OWNER_NAME_HOSTS.contains(&host.to_lowercase().as_str())
+}
+
+/// Recognises URLs for web pages inside a repository on GitHub, GitLab, Gitea/Forgejo,
+/// Bitbucket and SourceHut. Returns `None` for anything else, including plain clone URLs.
+pub fn parse_web_link(repo_str: &str) -> Option<WebLink> {
+    let (scheme, part_after_proto) = repo_str.split_once("://")?;
+
+    if !matches!(scheme, "http" | "https") {
+        return None;
+    }
+
+    let (authority, path) = part_after_proto.split_once('/')?;
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let components: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+
+    // GitLab puts everything that isn't the repository itself after /-/, which also allows for
+    // nested groups
+    let (repository, linked) = match components.iter().position(|c| *c == "-") {
+        Some(separator) if separator >= 2 => (
+            &components[..separator],
+            gitlab_linked(&components[separator + 1..]),
+        ),
+        Some(_) => return None,
+        // Elsewhere, `group/sub/src/app` could be a nested group's repository
+        None if components.len() > 2 && is_owner_name_host(authority) => {
+            (&components[..2], owner_name_linked(&components[2..])?)
+        }
+        None => return None,
+    };
+
+    Some(WebLink {
+        repo_url: format!("{scheme}://{authority}/{}", repository.join("/")),
+        linked,
+    })
+}
+
+/// Recognises Azure DevOps and Bitbucket Server by the hosts and path components their URLs
+/// use. Gerrit's `/a/` prefix could be a real owner elsewhere, so it's never detected.
+fn detect(repository: &Repository) -> Forge {
+    let components: Vec<&str> = repository.path.split('/').collect();
+
+    if matches!(
+        repository.host.as_str(),
+        "dev.azure.com" | "ssh.dev.azure.com"
+    ) || repository.host.ends_with(".visualstudio.com")
+        || components.contains(&"_git")
+    {
+        Forge::Azure
+    } else if components.len() >= 3 && components[0] == "scm" {
+        Forge::BitbucketServer
+    } else {
+        Forge::Generic
+    }
+}
+
+/// The repository's path without the parts `forge` adds to its URLs, e.g. `org/project/repo`
+/// for `https://dev.azure.com/org/project/_git/repo`.
+pub fn clean_path(repository: &Repository, forge: Forge) -> String {
+    let forge = match forge {
+        Forge::Auto => detect(repository),
+        forge => forge,
+    };
+
+    let path = repository.path.as_str();
+
+    match forge {
+        Forge::Azure => path
+            .strip_prefix("v3/")
+            .unwrap_or(path)
+            .split('/')
+            .filter(|component| !matches!(*component, "_git" | "DefaultCollection"))
+            .collect::<Vec<_>>()
+            .join("/"),
+        Forge::BitbucketServer => path.strip_prefix("scm/").unwrap_or(path).to_owned(),
+        Forge::Gerrit => path.strip_prefix("a/").unwrap_or(path).to_owned(),
+        Forge::Auto | Forge::Generic => path.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn web_link(repo_url: &str, components: &[&str]) -> Option<WebLink> {
+        Some(WebLink {
+            repo_url: repo_url.to_owned(),
+            linked: linked_ref(components),
+        })
+    }
+
+    fn pull_request(repo_url: &str, number: u64, head_ref: &str) -> Option<WebLink> {
+        Some(WebLink {
+            repo_url: repo_url.to_owned(),
+            linked: Some(Linked::PullRequest {
+                number,
+                head_ref: head_ref.to_owned(),
+            }),
+        })
+    }
+
+    #[rstest]
+    #[case::github_tree(
+        "https://github.com/owner/repo/tree/release/src/lib",
+        web_link("https://github.com/owner/repo", &["release", "src", "lib"])
+    )]
+    #[case::github_blob_with_fragment(
+        "https://github.com/owner/repo/blob/main/README.md#usage",
+        web_link("https://github.com/owner/repo", &["main", "README.md"])
+    )]
+    #[case::github_commit(
+        "https://github.com/owner/repo/commit/0123abc",
+        web_link("https://github.com/owner/repo", &["0123abc"])
+    )]
+    #[case::github_issues(
+        "https://github.com/owner/repo/issues?q=is%3Aopen",
+        web_link("https://github.com/owner/repo", &[])
+    )]
+    #[case::gitlab_blob(
+        "https://gitlab.corp/group/sub/proj/-/blob/main/README.md",
+        web_link("https://gitlab.corp/group/sub/proj", &["main", "README.md"])
+    )]
+    #[case::gitlab_issues(
+        "https://gitlab.corp/group/sub/proj/-/issues/12",
+        web_link("https://gitlab.corp/group/sub/proj", &[])
+    )]
+    #[case::gitea_branch(
+        "https://codeberg.org/owner/repo/src/branch/feature/x/docs/index.md",
+        web_link("https://codeberg.org/owner/repo", &["feature", "x", "docs", "index.md"])
+    )]
+    #[case::bitbucket_src(
+        "https://bitbucket.org/owner/repo/src/main/lib/",
+        web_link("https://bitbucket.org/owner/repo", &["main", "lib"])
+    )]
+    #[case::sourcehut_tree_item(
+        "https://git.sr.ht/~owner/repo/tree/main/item/src/main.rs",
+        web_link("https://git.sr.ht/~owner/repo", &["main"])
+    )]
+    #[case::sourcehut_log(
+        "https://git.sr.ht/~owner/repo/log/dev",
+        web_link("https://git.sr.ht/~owner/repo", &["dev"])
+    )]
+    #[case::github_pull_request_files(
+        "https://github.com/owner/repo/pull/123/files",
+        pull_request("https://github.com/owner/repo", 123, "refs/pull/123/head")
+    )]
+    #[case::gitea_pull_request(
+        "https://codeberg.org/owner/repo/pulls/7",
+        pull_request("https://codeberg.org/owner/repo", 7, "refs/pull/7/head")
+    )]
+    #[case::github_pull_requests(
+        "https://github.com/owner/repo/pulls",
+        web_link("https://github.com/owner/repo", &[])
+    )]
+    #[case::gitlab_merge_request(
+        "https://gitlab.corp/group/proj/-/merge_requests/45/diffs",
+        pull_request("https://gitlab.corp/group/proj", 45, "refs/merge-requests/45/head")
+    )]
+    #[case::bitbucket_pull_request(
+        "https://bitbucket.org/owner/repo/pull-requests/3",
+        web_link("https://bitbucket.org/owner/repo", &[])
+    )]
+    fn test_parse_web_link(#[case] repo_str: &str, #[case] expected: Option<WebLink>) {
+        assert_eq!(parse_web_link(repo_str), expected);
+    }
+
+    #[rstest]
+    #[case::ssh("git@github.com:owner/repo.git")]
+    #[case::https_clone_url("https://github.com/owner/repo.git")]
+    #[case::https_repository_page("https://github.com/owner/repo")]
+    #[case::gitlab_nested_group("https://gitlab.corp/group/sub/proj")]
+    #[case::ssh_url_with_tree("ssh://git@example.com/owner/repo/tree/main")]
+    #[case::gitlab_nested_group_ending_in_page("https://gitlab.corp.example.com/team/infra/src")]
+    #[case::gitlab_nested_group_called_wiki("https://gitlab.corp.example.com/team/infra/wiki")]
+    #[case::gitlab_nested_group_with_page_name_inside("https://gitlab.corp/org/team/releases/tool")]
+    #[case::gitlab_nested_group_with_src_inside("https://gitlab.corp/org/src/app")]
+    #[case::gitlab_nested_group_called_releases(
+        "https://gitlab.corp.example.com/team/infra/releases"
+    )]
+    fn test_parse_web_link_ignores_other_urls(#[case] repo_str: &str) {
+        assert_eq!(parse_web_link(repo_str), None);
+    }
+
+    #[rstest]
+    #[case::azure_https(
+        "https://dev.azure.com/org/project/_git/repo",
+        Forge::Auto,
+        "org/project/repo"
+    )]
+    #[case::azure_https_user(
+        "https://org@dev.azure.com/org/project/_git/repo",
+        Forge::Auto,
+        "org/project/repo"
+    )]
+    #[case::azure_ssh(
+        "git@ssh.dev.azure.com:v3/org/project/repo",
+        Forge::Auto,
+        "org/project/repo"
+    )]
+    #[case::azure_visualstudio(
+        "https://org.visualstudio.com/DefaultCollection/project/_git/repo",
+        Forge::Auto,
+        "project/repo"
+    )]
+    #[case::bitbucket_server_ssh(
+        "ssh://git@bitbucket.corp:7999/scm/proj/repo.git",
+        Forge::Auto,
+        "proj/repo"
+    )]
+    #[case::bitbucket_server_https(
+        "https://bitbucket.corp/scm/proj/repo.git",
+        Forge::Auto,
+        "proj/repo"
+    )]
+    #[case::gerrit_configured("https://gerrit.corp/a/tools/build", Forge::Gerrit, "tools/build")]
+    #[case::gerrit_not_detected("https://gerrit.corp/a/tools/build", Forge::Auto, "a/tools/build")]
+    #[case::github_owner_called_scm("git@github.com:scm/repo.git", Forge::Auto, "scm/repo")]
+    #[case::generic_keeps_path(
+        "https://dev.azure.com/org/project/_git/repo",
+        Forge::Generic,
+        "org/project/_git/repo"
+    )]
+    fn test_clean_path(#[case] repo_str: String, #[case] forge: Forge, #[case] expected: &str) {
+        let repository = Repository::try_from(&repo_str).unwrap();
+
+        assert_eq!(clean_path(&repository, forge), expected);
+    }
+}
@@ -0,0 +1,73 @@
+/// How well a repository matches a query, from worst to best.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Score {
+    /// The query's characters appear in order somewhere in the path
+    Subsequence,
+    /// The query appears somewhere in the path
+    Contains,
+    /// A host, owner or group in the path starts with the query
+    Owner,
+    /// The repository name contains the query
+    NameContains,
+    /// The repository name starts with the query
+    NamePrefix,
+    /// The repository name is the query
+    Name,
+}
+
+fn is_subsequence(query: &str, text: &str) -> bool {
+    let mut text = text.chars();
+    query.chars().all(|q| text.any(|t| t == q))
+}
+
+/// Scores a repository's `path` (which can start with its host) against `query`, ignoring
+/// case. `None` if it doesn't match at all.
+pub fn score(query: &str, path: &str) -> Option<Score> {
+    let query = query.to_lowercase();
+    let path = path.to_lowercase();
+
+    let (owners, name) = path.rsplit_once('/').unwrap_or(("", &path));
+
+    if name == query {
+        Some(Score::Name)
+    } else if name.starts_with(&query) {
+        Some(Score::NamePrefix)
+    } else if name.contains(&query) {
+        Some(Score::NameContains)
+    } else if owners.split('/').any(|owner| owner.starts_with(&query)) {
+        Some(Score::Owner)
+    } else if path.contains(&query) {
+        Some(Score::Contains)
+    } else if is_subsequence(&query, &path) {
+        Some(Score::Subsequence)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::name("rustlings", Some(Score::Name))]
+    #[case::name_ignores_case("RustLings", Some(Score::Name))]
+    #[case::name_prefix("rustl", Some(Score::NamePrefix))]
+    #[case::name_contains("lings", Some(Score::NameContains))]
+    #[case::owner("rust-lang", Some(Score::Owner))]
+    #[case::contains("hub.com/rust", Some(Score::Contains))]
+    #[case::subsequence("rlgs", Some(Score::Subsequence))]
+    #[case::no_match("cargo", None)]
+    fn test_score(#[case] query: &str, #[case] expected: Option<Score>) {
+        assert_eq!(score(query, "github.com/rust-lang/rustlings"), expected);
+    }
+
+    #[test]
+    fn test_score_orders_better_matches_higher() {
+        assert!(Score::Name > Score::NamePrefix);
+        assert!(Score::NameContains > Score::Owner);
+        assert!(Score::Contains > Score::Subsequence);
+    }
+}
@@ -1,12 +1,119 @@
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::process::{Command, Stdio};
 
+use serde::Serialize;
+
+use crate::commands;
 use crate::configuration::Configuration;
 use crate::errors::JCloneError;
 use crate::user_configuration::OutputStyle;
 
+/// A `url.<base>.insteadOf` rule from git's config.
+#[derive(Debug, PartialEq)]
+pub struct UrlRewrite {
+    pub base: String,
+    pub instead_of: String,
+}
+
+/// What `git status` and `git stash` say about a checkout.
+#[derive(Serialize, Debug, PartialEq, Default)]
+pub struct CheckoutStatus {
+    /// `None` when HEAD is detached.
+    pub branch: Option<String>,
+    pub upstream: Option<String>,
+    /// Commits on the current branch that aren't on its upstream.
+    pub ahead: usize,
+    /// Commits on the upstream that aren't on the current branch.
+    pub behind: usize,
+    /// Commits on any local branch that aren't on any remote.
+    pub unpushed: usize,
+    /// Staged, unstaged and conflicted files.
+    pub changed: usize,
+    pub untracked: usize,
+    pub stashes: usize,
+}
+
+impl CheckoutStatus {
+    pub fn is_dirty(&self) -> bool {
+        self.changed > 0 || self.untracked > 0
+    }
+
+    /// Whether there's work that only exists in this checkout, or nowhere for it to go.
+    /// Being behind isn't a problem, it just needs a pull.
+    pub fn needs_attention(&self) -> bool {
+        self.is_dirty()
+            || self.ahead > 0
+            || self.unpushed > 0
+            || self.stashes > 0
+            || self.branch.is_none()
+            || self.upstream.is_none()
+    }
+
+    /// Work that only exists in this checkout and would be lost with it, e.g. `["2 changed"]`.
+    pub fn local_work(&self) -> Vec<String> {
+        [
+            (
+                self.changed,
+                commands::plural(self.changed, "changed file", "changed files"),
+            ),
+            (
+                self.untracked,
+                commands::plural(self.untracked, "untracked file", "untracked files"),
+            ),
+            (
+                self.stashes,
+                commands::plural(self.stashes, "stash", "stashes"),
+            ),
+            (
+                self.unpushed,
+                commands::plural(self.unpushed, "unpushed commit", "unpushed commits"),
+            ),
+        ]
+        .into_iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(_, work)| work)
+        .collect()
+    }
+
+    /// Everything worth mentioning, e.g. `["2 changed", "ahead 1"]`. Empty when it's clean and
+    /// up to date.
+    pub fn notes(&self) -> Vec<String> {
+        let mut notes = vec![];
+        let count = |notes: &mut Vec<String>, n: usize, what: &str| {
+            if n > 0 {
+                notes.push(format!("{n} {what}"));
+            }
+        };
+
+        match (&self.branch, &self.upstream) {
+            (None, _) => notes.push(String::from("detached")),
+            (Some(_), None) => notes.push(String::from("no upstream")),
+            _ => (),
+        }
+
+        if self.ahead > 0 {
+            notes.push(format!("ahead {}", self.ahead));
+        }
+        if self.behind > 0 {
+            notes.push(format!("behind {}", self.behind));
+        }
+
+        count(&mut notes, self.unpushed, "unpushed");
+        count(&mut notes, self.changed, "changed");
+        count(&mut notes, self.untracked, "untracked");
+
+        if self.stashes > 0 {
+            notes.push(commands::plural(self.stashes, "stash", "stashes"));
+        }
+
+        notes
+    }
+}
+
 pub struct Git {
     git_executable: String,
+    env: BTreeMap<String, String>,
     repo_str: String,
     print_progress: bool,
 }
@@ -20,11 +127,17 @@ impl Git {
 
         Self {
             git_executable: config.git_executable.to_owned(),
+            env: config.git_env(),
             repo_str: repo_str.to_owned(),
             print_progress,
         }
     }
 
+    /// For commands in existing checkouts, which don't need a repository URL.
+    pub fn local(config: &Configuration) -> Self {
+        Self::new("", config)
+    }
+
     fn ls_remote(&self) -> Result<(), JCloneError> {
         let stderr = match self.print_progress {
             true => Stdio::inherit(),
@@ -32,6 +145,7 @@ impl Git {
         };
 
         let ls_remote_output = Command::new(&self.git_executable)
+            .envs(&self.env)
             .args(["ls-remote", "--heads"])
             .arg(&self.repo_str)
             .stdout(Stdio::null())
@@ -61,6 +175,7 @@ impl Git {
         };
 
         let clone_status = Command::new(&self.git_executable)
+            .envs(&self.env)
             .arg("clone")
             .arg(&self.repo_str)
             .arg(target_dir)
@@ -81,4 +196,900 @@ impl Git {
             }),
         }
     }
+
+    /// The URL of the `origin` remote of the repository at `repo_dir`, if it has one. This is the
+    /// URL as configured, before any `insteadOf` rules are applied.
+    pub fn remote_url(&self, repo_dir: &Path) -> Result<Option<String>, JCloneError> {
+        let output = Command::new(&self.git_executable)
+            .envs(&self.env)
+            .arg("-C")
+            .arg(repo_dir)
+            .args(["config", "--get", "remote.origin.url"])
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|err| JCloneError::GitSystem {
+                executable: self.git_executable.to_owned(),
+                command: "config",
+                source: err,
+            })?;
+
+        let url = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+
+        Ok(Some(url).filter(|url| output.status.success() && !url.is_empty()))
+    }
+
+    /// Runs a git command in `repo_dir` without any output, returning whether it succeeded.
+    fn succeeds_in(
+        &self,
+        repo_dir: &Path,
+        args: &[&str],
+        command: &'static str,
+    ) -> Result<bool, JCloneError> {
+        Command::new(&self.git_executable)
+            .envs(&self.env)
+            .arg("-C")
+            .arg(repo_dir)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .map_err(|err| JCloneError::GitSystem {
+                executable: self.git_executable.to_owned(),
+                command,
+                source: err,
+            })
+    }
+
+    /// Runs a git command in `repo_dir`, returning its output, or `None` if it failed.
+    fn output_in(
+        &self,
+        repo_dir: &Path,
+        args: &[&str],
+        command: &'static str,
+    ) -> Result<Option<String>, JCloneError> {
+        let output = Command::new(&self.git_executable)
+            .envs(&self.env)
+            .arg("-C")
+            .arg(repo_dir)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|err| JCloneError::GitSystem {
+                executable: self.git_executable.to_owned(),
+                command,
+                source: err,
+            })?;
+
+        Ok(output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+
+    /// Reads the branch, working tree and stashes of the checkout at `repo_dir`.
+    pub fn status(&self, repo_dir: &Path) -> Result<CheckoutStatus, JCloneError> {
+        // Skip refreshing the index, so this can run alongside whatever else is using the checkout
+        let status = self
+            .output_in(
+                repo_dir,
+                &[
+                    "--no-optional-locks",
+                    "status",
+                    "--porcelain=v2",
+                    "--branch",
+                    // Count every untracked file, whatever status.showUntrackedFiles says
+                    "--untracked-files=all",
+                ],
+                "status",
+            )?
+            .ok_or(JCloneError::GitUser {
+                command: "status",
+                message: "couldn't read the working tree",
+            })?;
+
+        let unpushed = self
+            .output_in(
+                repo_dir,
+                // Count commits on a detached HEAD too, which no branch keeps. A repository
+                // without commits has no HEAD yet, which isn't an error here.
+                &[
+                    "rev-list",
+                    "--count",
+                    "--ignore-missing",
+                    "--branches",
+                    "HEAD",
+                    "--not",
+                    "--remotes",
+                ],
+                "rev-list",
+            )?
+            .ok_or(JCloneError::GitUser {
+                command: "rev-list",
+                message: "couldn't count unpushed commits",
+            })?;
+
+        let stashes = self
+            .output_in(repo_dir, &["stash", "list"], "stash")?
+            .ok_or(JCloneError::GitUser {
+                command: "stash",
+                message: "couldn't list stashes",
+            })?;
+
+        Ok(CheckoutStatus {
+            unpushed: unpushed.trim().parse().unwrap_or_default(),
+            stashes: stashes.lines().count(),
+            ..parse_status(&status)
+        })
+    }
+
+    /// When the latest commit on any local branch of the checkout at `repo_dir` was made, in
+    /// seconds since the Unix epoch, or `None` if there aren't any commits.
+    pub fn last_commit_at(&self, repo_dir: &Path) -> Result<Option<u64>, JCloneError> {
+        let output = self
+            .output_in(
+                repo_dir,
+                &["log", "-1", "--branches", "--format=%ct"],
+                "log",
+            )?
+            .ok_or(JCloneError::GitUser {
+                command: "log",
+                message: "couldn't read the latest commit",
+            })?;
+
+        Ok(output.trim().parse().ok())
+    }
+
+    /// Fetches from the current branch's remote, or `origin`, in the checkout at `repo_dir`.
+    pub fn fetch(&self, repo_dir: &Path) -> Result<(), JCloneError> {
+        match self.succeeds_in(repo_dir, &["fetch", "--quiet"], "fetch")? {
+            true => Ok(()),
+            false => Err(JCloneError::GitUser {
+                command: "fetch",
+                message: "couldn't fetch from the remote",
+            }),
+        }
+    }
+
+    /// Fast-forwards the current branch of the checkout at `repo_dir` to its upstream.
+    pub fn fast_forward(&self, repo_dir: &Path) -> Result<(), JCloneError> {
+        match self.succeeds_in(
+            repo_dir,
+            &["merge", "--ff-only", "--quiet", "@{upstream}"],
+            "merge",
+        )? {
+            true => Ok(()),
+            false => Err(JCloneError::GitUser {
+                command: "merge",
+                message: "couldn't fast-forward to the upstream",
+            }),
+        }
+    }
+
+    fn has_ref(&self, repo_dir: &Path, full_name: &str) -> Result<bool, JCloneError> {
+        self.succeeds_in(
+            repo_dir,
+            &["show-ref", "--verify", "--quiet", full_name],
+            "show-ref",
+        )
+    }
+
+    /// Checks out what a web link points to in the clone at `repo_dir`: the longest prefix of
+    /// `components` naming a branch or tag, or else a commit named by the first component.
+    /// Returns what was checked out, or `None` if nothing matched.
+    pub fn checkout_linked_ref(
+        &self,
+        repo_dir: &Path,
+        components: &[String],
+    ) -> Result<Option<String>, JCloneError> {
+        let mut checkout = None;
+
+        for length in (1..=components.len()).rev() {
+            let name = components[..length].join("/");
+
+            if self.has_ref(repo_dir, &format!("refs/remotes/origin/{name}"))? {
+                checkout = Some((name, false));
+                break;
+            }
+
+            if self.has_ref(repo_dir, &format!("refs/tags/{name}"))? {
+                checkout = Some((name, true));
+                break;
+            }
+        }
+
+        if checkout.is_none()
+            && let Some(first) = components.first()
+            && self.succeeds_in(
+                repo_dir,
+                &[
+                    "rev-parse",
+                    "--verify",
+                    "--quiet",
+                    &format!("{first}^{{commit}}"),
+                ],
+                "rev-parse",
+            )?
+        {
+            checkout = Some((first.to_owned(), true));
+        }
+
+        let Some((name, detach)) = checkout else {
+            return Ok(None);
+        };
+
+        let args = match detach {
+            true => vec!["checkout", "--detach", &name],
+            false => vec!["checkout", &name],
+        };
+
+        match self.succeeds_in(repo_dir, &args, "checkout")? {
+            true => Ok(Some(name)),
+            false => Err(JCloneError::GitUser {
+                command: "checkout",
+                message: "couldn't check out the linked ref",
+            }),
+        }
+    }
+
+    /// Fetches `head_ref` from `origin` into the local `branch` of the repository at `repo_dir`
+    /// and checks it out. A `branch` that already exists is only moved forward, so commits made
+    /// on it aren't lost.
+    pub fn fetch_pull_request(
+        &self,
+        repo_dir: &Path,
+        head_ref: &str,
+        branch: &str,
+    ) -> Result<(), JCloneError> {
+        let stdio = match self.print_progress {
+            true => || Stdio::inherit(),
+            false => || Stdio::null(),
+        };
+
+        let fetch_status = Command::new(&self.git_executable)
+            .envs(&self.env)
+            .arg("-C")
+            .arg(repo_dir)
+            .args(["fetch", "origin", head_ref])
+            .stdout(stdio())
+            .stderr(stdio())
+            .status();
+
+        match fetch_status {
+            Ok(status) if status.success() => (),
+            Ok(_) => {
+                return Err(JCloneError::GitUser {
+                    command: "fetch",
+                    message: "couldn't fetch the pull request",
+                });
+            }
+            Err(err) => {
+                return Err(JCloneError::GitSystem {
+                    executable: self.git_executable.to_owned(),
+                    command: "fetch",
+                    source: err,
+                });
+            }
+        }
+
+        let branch_ref = format!("refs/heads/{branch}");
+        let exists = self.succeeds_in(
+            repo_dir,
+            &["rev-parse", "--verify", "--quiet", &branch_ref],
+            "rev-parse",
+        )?;
+
+        if exists
+            && !self.succeeds_in(
+                repo_dir,
+                &["merge-base", "--is-ancestor", &branch_ref, "FETCH_HEAD"],
+                "merge-base",
+            )?
+        {
+            return Err(JCloneError::PullRequestBranchDiverged(branch.to_owned()));
+        }
+
+        match self.succeeds_in(
+            repo_dir,
+            &["checkout", "-B", branch, "FETCH_HEAD"],
+            "checkout",
+        )? {
+            true => Ok(()),
+            false => Err(JCloneError::GitUser {
+                command: "checkout",
+                message: "couldn't check out the pull request",
+            }),
+        }
+    }
+
+    /// Sets a value in the local git config of the repository at `repo_dir`.
+    pub fn set_config(&self, repo_dir: &Path, key: &str, value: &str) -> Result<(), JCloneError> {
+        let config_status = Command::new(&self.git_executable)
+            .envs(&self.env)
+            .arg("-C")
+            .arg(repo_dir)
+            .args(["config", "--local", key, value])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        match config_status {
+            Ok(status) if status.success() => Ok(()),
+            Ok(_) => Err(JCloneError::GitUser {
+                command: "config",
+                message: "couldn't set local config",
+            }),
+            Err(err) => Err(JCloneError::GitSystem {
+                executable: self.git_executable.to_owned(),
+                command: "config",
+                source: err,
+            }),
+        }
+    }
+}
+
+/// Parses `git status --porcelain=v2 --branch`.
+fn parse_status(output: &str) -> CheckoutStatus {
+    let mut status = CheckoutStatus::default();
+
+    for line in output.lines() {
+        if let Some(head) = line.strip_prefix("# branch.head ") {
+            status.branch = Some(head.to_owned()).filter(|head| head != "(detached)");
+        } else if let Some(upstream) = line.strip_prefix("# branch.upstream ") {
+            status.upstream = Some(upstream.to_owned());
+        } else if let Some(counts) = line.strip_prefix("# branch.ab ") {
+            // e.g. `+1 -2`
+            let mut counts = counts.split(' ').map(|count| {
+                count
+                    .get(1..)
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_default()
+            });
+            status.ahead = counts.next().unwrap_or_default();
+            status.behind = counts.next().unwrap_or_default();
+        } else if line.starts_with("? ") {
+            status.untracked += 1;
+        } else if line.starts_with(['1', '2', 'u']) {
+            status.changed += 1;
+        }
+    }
+
+    status
+}
+
+fn parse_url_rewrites(output: &str) -> Vec<UrlRewrite> {
+    // With --null, each entry is the key and value separated by a newline
+    output
+        .split('\0')
+        .filter_map(|entry| {
+            let (key, instead_of) = entry.split_once('\n')?;
+            let base = key.strip_prefix("url.")?.strip_suffix(".insteadof")?;
+
+            Some(UrlRewrite {
+                base: base.to_owned(),
+                instead_of: instead_of.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Reads the `url.<base>.insteadOf` rules git would apply when cloning. `pushInsteadOf` rules
+/// are left out, as they don't change where git clones from.
+pub fn url_rewrites(config: &Configuration) -> Result<Vec<UrlRewrite>, JCloneError> {
+    // Run outside any repository, as a clone never uses the rules in the current one's config
+    let config_output = Command::new(&config.git_executable)
+        .envs(config.git_env())
+        .arg("-C")
+        .arg("/")
+        .args(["config", "--null", "--get-regexp", r"^url\..*\.insteadof$"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+
+    match config_output {
+        // Exit code 1 means there just weren't any rules
+        Ok(output) if output.status.success() || output.status.code() == Some(1) => {
+            Ok(parse_url_rewrites(&String::from_utf8_lossy(&output.stdout)))
+        }
+        Ok(_) => Err(JCloneError::GitUser {
+            command: "config",
+            message: "couldn't read url.<base>.insteadOf rules",
+        }),
+        Err(err) => Err(JCloneError::GitSystem {
+            executable: config.git_executable.to_owned(),
+            command: "config",
+            source: err,
+        }),
+    }
+}
+
+/// Rewrites `url` the way git does, using the rule with the longest matching `insteadOf`.
+pub fn rewrite_url(url: &str, rewrites: &[UrlRewrite]) -> String {
+    rewrites
+        .iter()
+        .filter(|rewrite| url.starts_with(&rewrite.instead_of))
+        .max_by_key(|rewrite| rewrite.instead_of.len())
+        .map_or_else(
+            || url.to_owned(),
+            |rewrite| format!("{}{}", rewrite.base, &url[rewrite.instead_of.len()..]),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use rstest::rstest;
+
+    use crate::user_configuration::{UserConfiguration, UserHostConfiguration};
+
+    use super::*;
+
+    fn rewrite(base: &str, instead_of: &str) -> UrlRewrite {
+        UrlRewrite {
+            base: base.to_owned(),
+            instead_of: instead_of.to_owned(),
+        }
+    }
+
+    /// Configuration for running git with only the given global config file.
+    fn isolated_config(global_config: &Path) -> Configuration {
+        let user_config = UserConfiguration {
+            variants: vec![UserHostConfiguration {
+                host: String::from("example.com"),
+                env: Some(BTreeMap::from([
+                    (
+                        String::from("GIT_CONFIG_GLOBAL"),
+                        global_config.to_string_lossy().into_owned(),
+                    ),
+                    (String::from("GIT_CONFIG_NOSYSTEM"), String::from("1")),
+                ])),
+                ..UserHostConfiguration::default()
+            }],
+            ..UserConfiguration::default()
+        };
+
+        Configuration::from_user_configuration(
+            user_config,
+            &String::from("example.com"),
+            String::from("/home/ferris"),
+        )
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case::no_match("git@example.com:my_repo", "git@example.com:my_repo")]
+    #[case::short_name("corp:team/app", "https://gitlab.corp.example.com/team/app")]
+    #[case::longest_wins(
+        "https://github.com/rust-lang/rustlings",
+        "git@github.com:rust-lang/rustlings"
+    )]
+    fn test_rewrite_url(#[case] url: &str, #[case] expected: &str) {
+        let rewrites = vec![
+            rewrite("https://gitlab.corp.example.com/", "corp:"),
+            rewrite("https://mirror.example.com/", "https://"),
+            rewrite("git@github.com:", "https://github.com/"),
+        ];
+
+        assert_eq!(rewrite_url(url, &rewrites), expected);
+    }
+
+    #[test]
+    fn test_parse_url_rewrites() {
+        let output = "url.https://gitlab.corp.example.com/.insteadof\ncorp:\0url.git@github.com:.insteadof\nhttps://github.com/\0";
+
+        assert_eq!(
+            parse_url_rewrites(output),
+            vec![
+                rewrite("https://gitlab.corp.example.com/", "corp:"),
+                rewrite("git@github.com:", "https://github.com/"),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::clean(
+        "# branch.oid 1234\n# branch.head main\n# branch.upstream origin/main\n# branch.ab +0 -0\n",
+        CheckoutStatus { branch: Some(String::from("main")), upstream: Some(String::from("origin/main")), ..CheckoutStatus::default() }
+    )]
+    #[case::ahead_behind_and_dirty(
+        "# branch.head main\n# branch.upstream origin/main\n# branch.ab +2 -1\n1 .M N... 100644 100644 100644 1234 1234 src/lib.rs\n2 R. N... 100644 100644 100644 1234 1234 R100 new.rs\told.rs\nu UU N... 100644 100644 100644 100644 1234 1234 1234 conflict.rs\n? notes.txt\n",
+        CheckoutStatus {
+            branch: Some(String::from("main")),
+            upstream: Some(String::from("origin/main")),
+            ahead: 2,
+            behind: 1,
+            changed: 3,
+            untracked: 1,
+            ..CheckoutStatus::default()
+        }
+    )]
+    #[case::detached(
+        "# branch.oid 1234\n# branch.head (detached)\n",
+        CheckoutStatus::default()
+    )]
+    #[case::no_upstream(
+        "# branch.oid (initial)\n# branch.head main\n",
+        CheckoutStatus { branch: Some(String::from("main")), ..CheckoutStatus::default() }
+    )]
+    fn test_parse_status(#[case] output: &str, #[case] expected: CheckoutStatus) {
+        assert_eq!(parse_status(output), expected);
+    }
+
+    #[rstest]
+    #[case::clean(CheckoutStatus { branch: Some(String::from("main")), upstream: Some(String::from("origin/main")), ..CheckoutStatus::default() }, &[], false)]
+    #[case::behind(CheckoutStatus { branch: Some(String::from("main")), upstream: Some(String::from("origin/main")), behind: 3, ..CheckoutStatus::default() }, &["behind 3"], false)]
+    #[case::detached_with_stash(CheckoutStatus { stashes: 1, ..CheckoutStatus::default() }, &["detached", "1 stash"], true)]
+    #[case::unpushed_work(
+        CheckoutStatus { branch: Some(String::from("main")), ahead: 1, unpushed: 2, changed: 4, stashes: 2, ..CheckoutStatus::default() },
+        &["no upstream", "ahead 1", "2 unpushed", "4 changed", "2 stashes"],
+        true
+    )]
+    fn test_checkout_status_notes(
+        #[case] status: CheckoutStatus,
+        #[case] expected_notes: &[&str],
+        #[case] expected_attention: bool,
+    ) {
+        assert_eq!(status.notes(), expected_notes);
+        assert_eq!(status.needs_attention(), expected_attention);
+    }
+
+    #[rstest]
+    #[case::clean(CheckoutStatus { ahead: 1, behind: 2, ..CheckoutStatus::default() }, &[])]
+    #[case::one_of_each(
+        CheckoutStatus { changed: 1, untracked: 1, stashes: 1, unpushed: 1, ..CheckoutStatus::default() },
+        &["1 changed file", "1 untracked file", "1 stash", "1 unpushed commit"]
+    )]
+    #[case::several(CheckoutStatus { untracked: 3, unpushed: 2, ..CheckoutStatus::default() }, &["3 untracked files", "2 unpushed commits"])]
+    fn test_checkout_status_local_work(#[case] status: CheckoutStatus, #[case] expected: &[&str]) {
+        assert_eq!(status.local_work(), expected);
+    }
+
+    #[test]
+    fn test_url_rewrites_reads_git_config() {
+        let home = tempfile::tempdir().unwrap();
+        let global_config = home.path().join(".gitconfig");
+        fs::write(
+            &global_config,
+            "[url \"https://gitlab.corp.example.com/\"]\n\tinsteadOf = corp:\n\tpushInsteadOf = push:\n",
+        )
+        .unwrap();
+
+        let rewrites = url_rewrites(&isolated_config(&global_config)).unwrap();
+
+        assert_eq!(
+            rewrites,
+            vec![rewrite("https://gitlab.corp.example.com/", "corp:")]
+        );
+    }
+
+    #[test]
+    fn test_url_rewrites_without_any_rules() {
+        let home = tempfile::tempdir().unwrap();
+        let global_config = home.path().join(".gitconfig");
+        fs::write(&global_config, "").unwrap();
+
+        let rewrites = url_rewrites(&isolated_config(&global_config)).unwrap();
+
+        assert!(rewrites.is_empty());
+    }
+
+    /// Runs git in `dir` ignoring any user or system config.
+    fn run_git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .args([
+                "-c",
+                "user.name=Ferris",
+                "-c",
+                "user.email=ferris@example.com",
+            ])
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    /// A bare repository with `main`, a `release/1.0` branch, a `v1.0` tag and a pull and merge
+    /// request, plus an empty global git config to clone it with.
+    fn remote_with_refs(dir: &Path) -> (PathBuf, PathBuf) {
+        let remote = dir.join("remote.git");
+        let work = dir.join("work");
+        fs::create_dir(&work).unwrap();
+        run_git(&work, &["init", "-q", "-b", "main"]);
+        run_git(&work, &["commit", "-q", "--allow-empty", "-m", "First"]);
+        run_git(&work, &["branch", "release/1.0"]);
+        run_git(&work, &["tag", "v1.0"]);
+        run_git(&work, &["commit", "-q", "--allow-empty", "-m", "Second"]);
+        run_git(dir, &["clone", "-q", "--bare", "work", "remote.git"]);
+        run_git(&remote, &["update-ref", "refs/pull/1/head", "v1.0"]);
+        run_git(
+            &remote,
+            &["update-ref", "refs/merge-requests/2/head", "main"],
+        );
+
+        let global_config = dir.join(".gitconfig");
+        fs::write(&global_config, "").unwrap();
+
+        (remote, global_config)
+    }
+
+    #[rstest]
+    #[case::branch_with_path(&["release", "1.0", "src", "lib"], Some("release/1.0"), "release/1.0")]
+    #[case::default_branch(&["main", "README.md"], Some("main"), "main")]
+    #[case::tag(&["v1.0"], Some("v1.0"), "HEAD")]
+    #[case::unknown(&["nope", "README.md"], None, "main")]
+    fn test_checkout_linked_ref(
+        #[case] components: &[&str],
+        #[case] expected: Option<&str>,
+        #[case] expected_head: &str,
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let (remote, global_config) = remote_with_refs(dir.path());
+        let config = Configuration {
+            output_style: OutputStyle::Quiet,
+            ..isolated_config(&global_config)
+        };
+        let git = Git::new(&remote.to_string_lossy(), &config);
+        let checkout = dir.path().join("checkout");
+        let components: Vec<String> = components.iter().map(|c| c.to_string()).collect();
+
+        git.clone(&checkout).unwrap();
+
+        assert_eq!(
+            git.checkout_linked_ref(&checkout, &components).unwrap(),
+            expected.map(String::from)
+        );
+        assert_eq!(
+            run_git(&checkout, &["rev-parse", "--abbrev-ref", "HEAD"]),
+            expected_head
+        );
+    }
+
+    #[test]
+    fn test_checkout_linked_ref_finds_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let (remote, global_config) = remote_with_refs(dir.path());
+        let config = Configuration {
+            output_style: OutputStyle::Quiet,
+            ..isolated_config(&global_config)
+        };
+        let git = Git::new(&remote.to_string_lossy(), &config);
+        let checkout = dir.path().join("checkout");
+        let first_commit = run_git(&remote, &["rev-parse", "--short", "v1.0"]);
+
+        git.clone(&checkout).unwrap();
+
+        assert_eq!(
+            git.checkout_linked_ref(&checkout, &[first_commit.to_owned()])
+                .unwrap(),
+            Some(first_commit.to_owned())
+        );
+        assert_eq!(
+            run_git(&checkout, &["rev-parse", "--short", "HEAD"]),
+            first_commit
+        );
+    }
+
+    #[rstest]
+    #[case::github("refs/pull/1/head", "pr-1", "v1.0")]
+    #[case::gitlab("refs/merge-requests/2/head", "pr-2", "main")]
+    fn test_fetch_pull_request(
+        #[case] head_ref: &str,
+        #[case] branch: &str,
+        #[case] expected_commit: &str,
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let (remote, global_config) = remote_with_refs(dir.path());
+        let config = Configuration {
+            output_style: OutputStyle::Quiet,
+            ..isolated_config(&global_config)
+        };
+        let git = Git::new(&remote.to_string_lossy(), &config);
+        let checkout = dir.path().join("checkout");
+
+        git.clone(&checkout).unwrap();
+        git.fetch_pull_request(&checkout, head_ref, branch).unwrap();
+
+        assert_eq!(
+            run_git(&checkout, &["rev-parse", "--abbrev-ref", "HEAD"]),
+            branch
+        );
+        assert_eq!(
+            git.remote_url(&checkout).unwrap(),
+            Some(remote.to_string_lossy().into_owned())
+        );
+        assert_eq!(
+            run_git(&checkout, &["rev-parse", "HEAD"]),
+            run_git(&remote, &["rev-parse", expected_commit])
+        );
+    }
+
+    #[test]
+    fn test_fetch_pull_request_updates_existing_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let (remote, global_config) = remote_with_refs(dir.path());
+        let config = Configuration {
+            output_style: OutputStyle::Quiet,
+            ..isolated_config(&global_config)
+        };
+        let git = Git::new(&remote.to_string_lossy(), &config);
+        let checkout = dir.path().join("checkout");
+
+        git.clone(&checkout).unwrap();
+        git.fetch_pull_request(&checkout, "refs/pull/1/head", "pr-1")
+            .unwrap();
+        run_git(&remote, &["update-ref", "refs/pull/1/head", "main"]);
+        git.fetch_pull_request(&checkout, "refs/pull/1/head", "pr-1")
+            .unwrap();
+
+        assert_eq!(
+            run_git(&checkout, &["rev-parse", "HEAD"]),
+            run_git(&remote, &["rev-parse", "main"])
+        );
+    }
+
+    #[test]
+    fn test_fetch_pull_request_keeps_local_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let (remote, global_config) = remote_with_refs(dir.path());
+        let config = Configuration {
+            output_style: OutputStyle::Quiet,
+            ..isolated_config(&global_config)
+        };
+        let git = Git::new(&remote.to_string_lossy(), &config);
+        let checkout = dir.path().join("checkout");
+
+        git.clone(&checkout).unwrap();
+        git.fetch_pull_request(&checkout, "refs/pull/1/head", "pr-1")
+            .unwrap();
+        run_git(
+            &checkout,
+            &["commit", "-q", "--allow-empty", "-m", "Review"],
+        );
+        let local_commit = run_git(&checkout, &["rev-parse", "HEAD"]);
+
+        assert!(matches!(
+            git.fetch_pull_request(&checkout, "refs/pull/1/head", "pr-1"),
+            Err(JCloneError::PullRequestBranchDiverged(branch)) if branch == "pr-1"
+        ));
+        assert_eq!(run_git(&checkout, &["rev-parse", "pr-1"]), local_commit);
+    }
+
+    #[test]
+    fn test_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let (remote, global_config) = remote_with_refs(dir.path());
+        let config = Configuration {
+            output_style: OutputStyle::Quiet,
+            ..isolated_config(&global_config)
+        };
+        let git = Git::new(&remote.to_string_lossy(), &config);
+        let checkout = dir.path().join("checkout");
+
+        git.clone(&checkout).unwrap();
+        run_git(&checkout, &["commit", "-q", "--allow-empty", "-m", "Third"]);
+        run_git(&checkout, &["branch", "topic"]);
+        run_git(
+            &checkout,
+            &["commit", "-q", "--allow-empty", "-m", "Fourth"],
+        );
+        fs::write(checkout.join("stashed.txt"), "").unwrap();
+        run_git(&checkout, &["stash", "-q", "--include-untracked"]);
+        fs::write(checkout.join("new.txt"), "").unwrap();
+
+        assert_eq!(
+            git.status(&checkout).unwrap(),
+            CheckoutStatus {
+                branch: Some(String::from("main")),
+                upstream: Some(String::from("origin/main")),
+                ahead: 2,
+                unpushed: 2,
+                untracked: 1,
+                stashes: 1,
+                ..CheckoutStatus::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_status_counts_commits_on_detached_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let (remote, global_config) = remote_with_refs(dir.path());
+        let config = Configuration {
+            output_style: OutputStyle::Quiet,
+            ..isolated_config(&global_config)
+        };
+        let git = Git::new(&remote.to_string_lossy(), &config);
+        let checkout = dir.path().join("checkout");
+
+        git.clone(&checkout).unwrap();
+        run_git(&checkout, &["checkout", "-q", "--detach"]);
+        run_git(&checkout, &["commit", "-q", "--allow-empty", "-m", "Lost"]);
+
+        assert_eq!(git.status(&checkout).unwrap().unpushed, 1);
+    }
+
+    #[test]
+    fn test_status_without_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, global_config) = remote_with_refs(dir.path());
+        let git = Git::local(&isolated_config(&global_config));
+        let empty = dir.path().join("empty");
+        fs::create_dir(&empty).unwrap();
+        run_git(&empty, &["init", "-q"]);
+
+        assert_eq!(git.status(&empty).unwrap().unpushed, 0);
+    }
+
+    #[test]
+    fn test_last_commit_at() {
+        let dir = tempfile::tempdir().unwrap();
+        let (remote, global_config) = remote_with_refs(dir.path());
+        let git = Git::local(&isolated_config(&global_config));
+        let empty = dir.path().join("empty");
+        fs::create_dir(&empty).unwrap();
+        run_git(&empty, &["init", "-q"]);
+
+        assert_eq!(
+            git.last_commit_at(&remote).unwrap(),
+            Some(
+                run_git(&remote, &["log", "-1", "--format=%ct", "main"])
+                    .parse()
+                    .unwrap()
+            )
+        );
+        assert_eq!(git.last_commit_at(&empty).unwrap(), None);
+    }
+
+    #[test]
+    fn test_status_outside_checkout() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, global_config) = remote_with_refs(dir.path());
+        let git = Git::local(&isolated_config(&global_config));
+
+        assert!(matches!(
+            git.status(&dir.path().join("missing")),
+            Err(JCloneError::GitUser {
+                command: "status",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_fetch_and_fast_forward() {
+        let dir = tempfile::tempdir().unwrap();
+        let (remote, global_config) = remote_with_refs(dir.path());
+        let config = Configuration {
+            output_style: OutputStyle::Quiet,
+            ..isolated_config(&global_config)
+        };
+        let git = Git::new(&remote.to_string_lossy(), &config);
+        let checkout = dir.path().join("checkout");
+
+        git.clone(&checkout).unwrap();
+        run_git(
+            &dir.path().join("work"),
+            &["commit", "-q", "--allow-empty", "-m", "Third"],
+        );
+        run_git(&remote, &["fetch", "-q", "../work", "main:main"]);
+
+        git.fetch(&checkout).unwrap();
+        assert_eq!(git.status(&checkout).unwrap().behind, 1);
+
+        git.fast_forward(&checkout).unwrap();
+        assert_eq!(
+            run_git(&checkout, &["rev-parse", "HEAD"]),
+            run_git(&remote, &["rev-parse", "main"])
+        );
+    }
 }
@@ -0,0 +1,219 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::errors::{JCloneError, JCloneResult};
+use crate::repository::Repository;
+use crate::user_configuration::OutputStyle;
+
+/// Values available to hook commands as `{path}`, `{host}` and `{name}`.
+pub struct HookVariables<'a> {
+    pub path: &'a Path,
+    pub host: &'a str,
+    pub name: &'a str,
+}
+
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Replaces template variables in a hook command with shell-quoted values, leaving any other
+/// braces (e.g. `${HOME}`) alone.
+pub fn expand(template: &str, variables: &HookVariables) -> String {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let placeholder = rest.find('}').map_or("", |end| &rest[..=end]);
+
+        let value = match placeholder {
+            "{path}" => Some(variables.path.to_string_lossy()),
+            "{host}" => Some(variables.host.into()),
+            "{name}" => Some(variables.name.into()),
+            _ => None,
+        };
+
+        match value {
+            Some(value) => {
+                expanded.push_str(&shell_quote(&value));
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                expanded.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Runs the pre-clone check with `sh`, passing the repository as JSON on stdin. The clone is
+/// refused if the command fails.
+pub fn run_pre_clone(
+    command: &str,
+    repository: &Repository,
+    output_style: &OutputStyle,
+) -> JCloneResult<()> {
+    let veto = |reason: String| JCloneError::PreCloneVeto {
+        command: command.to_owned(),
+        reason,
+    };
+
+    // The check's own explanation is worth showing whenever anything is
+    let stderr = match output_style {
+        OutputStyle::Quiet => Stdio::null(),
+        _ => Stdio::inherit(),
+    };
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(stderr)
+        .spawn()
+        .map_err(|err| veto(err.to_string()))?;
+
+    let json = serde_json::to_string(repository).map_err(|err| veto(err.to_string()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The check is free to ignore its input, so a closed pipe isn't an error
+        let _ = stdin.write_all(json.as_bytes());
+    }
+
+    match child.wait() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(veto(status.to_string())),
+        Err(err) => Err(veto(err.to_string())),
+    }
+}
+
+/// Runs each post-clone command with `sh` in the new checkout, stopping at the first failure.
+pub fn run_post_clone(
+    commands: &[String],
+    variables: &HookVariables,
+    output_style: &OutputStyle,
+) -> JCloneResult<()> {
+    let stdio = match output_style {
+        OutputStyle::Default => || Stdio::inherit(),
+        _ => || Stdio::null(),
+    };
+
+    for command in commands {
+        let command = expand(command, variables);
+
+        if matches!(output_style, OutputStyle::Default | OutputStyle::NoGit) {
+            println!("🪝 Running {command}");
+        }
+
+        let hook_status = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .current_dir(variables.path)
+            .stdout(stdio())
+            .stderr(stdio())
+            .status();
+
+        let reason = match hook_status {
+            Ok(status) if status.success() => continue,
+            Ok(status) => status.to_string(),
+            Err(err) => err.to_string(),
+        };
+
+        return Err(JCloneError::PostCloneHook {
+            command,
+            path: variables.path.to_owned(),
+            reason,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::no_variables("direnv allow", "direnv allow")]
+    #[case::all_variables(
+        "echo {host} {name} > {path}/.info",
+        "echo 'example.com' 'my_repo' > '/home/ferris/src/my_repo'/.info"
+    )]
+    #[case::other_braces("echo ${HOME} {unknown} {", "echo ${HOME} {unknown} {")]
+    fn test_expand_replaces_only_known_variables(#[case] template: &str, #[case] expected: &str) {
+        let variables = HookVariables {
+            path: Path::new("/home/ferris/src/my_repo"),
+            host: "example.com",
+            name: "my_repo",
+        };
+
+        assert_eq!(expand(template, &variables), expected);
+    }
+
+    #[test]
+    fn test_expand_quotes_values_safely() {
+        let variables = HookVariables {
+            path: Path::new("/home/ferris/src/it's {host}"),
+            host: "example.com",
+            name: "$(rm -rf ~)",
+        };
+
+        assert_eq!(
+            expand("echo {path} {name}", &variables),
+            r"echo '/home/ferris/src/it'\''s {host}' '$(rm -rf ~)'"
+        );
+    }
+
+    #[rstest]
+    #[case::allows(r#"grep -q '"host":"example.com"'"#, true)]
+    #[case::vetoes(r#"grep -q '"host":"example.net"'"#, false)]
+    #[case::ignores_stdin("true", true)]
+    fn test_run_pre_clone_receives_repository_as_json(
+        #[case] command: &str,
+        #[case] allowed: bool,
+    ) {
+        let repository = Repository {
+            host: String::from("example.com"),
+            path: String::from("my_user/my_repo"),
+        };
+
+        let result = run_pre_clone(command, &repository, &OutputStyle::Quiet);
+
+        assert_eq!(result.is_ok(), allowed);
+    }
+
+    #[test]
+    fn test_run_post_clone_runs_in_checkout_and_stops_at_failure() {
+        let checkout = tempfile::tempdir().unwrap();
+        let variables = HookVariables {
+            path: checkout.path(),
+            host: "example.com",
+            name: "my_repo",
+        };
+        let commands = vec![
+            String::from("echo {name} > first"),
+            String::from("exit 3"),
+            String::from("touch never"),
+        ];
+
+        let result = run_post_clone(&commands, &variables, &OutputStyle::Quiet);
+
+        assert!(matches!(
+            result,
+            Err(JCloneError::PostCloneHook { command, .. }) if command == "exit 3"
+        ));
+        assert_eq!(
+            std::fs::read_to_string(checkout.path().join("first")).unwrap(),
+            "my_repo\n"
+        );
+        assert!(!checkout.path().join("never").exists());
+    }
+}
@@ -0,0 +1,255 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{JCloneError, JCloneResult};
+use crate::repository::Repository;
+
+const INDEX_VERSION: u32 = 1;
+
+/// A repository jclone has cloned.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct IndexEntry {
+    /// The URL git cloned from.
+    pub url: String,
+    pub repository: Repository,
+    pub dir: PathBuf,
+    /// When it was cloned, in seconds since the Unix epoch.
+    pub cloned_at: u64,
+    /// Host of the `[[variant]]` that applied, if any.
+    pub variant: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexFile {
+    version: u32,
+    repositories: Vec<IndexEntry>,
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// When `path` was last modified, in seconds since the Unix epoch. Zero if it can't be read.
+pub fn modified_at(path: &Path) -> u64 {
+    fs::symlink_metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// When `.git` in the checkout at `dir` last changed, which git commands like commit and
+/// checkout do, in seconds since the Unix epoch. Zero if it can't be read.
+pub fn git_modified_at(dir: &Path) -> u64 {
+    modified_at(&dir.join(".git"))
+}
+
+/// The index of cloned repositories, kept as JSON in jclone's state directory.
+pub struct Index {
+    path: PathBuf,
+}
+
+impl Index {
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The index in `$XDG_STATE_HOME/jclone`, or `$HOME/.local/state/jclone` if that isn't set.
+    pub fn try_default() -> JCloneResult<Self> {
+        let state_dir = match env::var("XDG_STATE_HOME") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var("HOME")?).join(".local/state"),
+        };
+
+        Ok(Self::at(state_dir.join("jclone/index.json")))
+    }
+
+    /// Applies `change` to the default index. The index is only a cache that `jclone index` can
+    /// rebuild, so if that fails this warns rather than failing whatever changed the checkouts.
+    pub fn update_default(change: impl FnOnce(&Self) -> JCloneResult<()>) {
+        if let Err(err) = Self::try_default().and_then(|index| change(&index)) {
+            eprintln!("⚠️ Couldn't update the index: {err}");
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads every entry. A missing index is empty.
+    pub fn load(&self) -> JCloneResult<Vec<IndexEntry>> {
+        let json = match fs::read_to_string(&self.path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(JCloneError::IndexLoad(self.path.to_owned(), err)),
+        };
+
+        let file: IndexFile = serde_json::from_str(&json)
+            .map_err(|err| JCloneError::IndexParse(self.path.to_owned(), err))?;
+
+        Ok(file.repositories)
+    }
+
+    /// Saves the entries from `entries` while holding a lock file, so other jclone processes
+    /// wait their turn. The index is replaced in one rename, so readers never see half of it.
+    fn write_locked(
+        &self,
+        entries: impl FnOnce() -> JCloneResult<Vec<IndexEntry>>,
+    ) -> JCloneResult<()> {
+        let write_error = |err| JCloneError::IndexWrite(self.path.to_owned(), err);
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(write_error)?;
+        }
+
+        let lock = File::create(self.path.with_extension("lock")).map_err(write_error)?;
+        lock.lock().map_err(write_error)?;
+
+        let json = serde_json::to_string_pretty(&IndexFile {
+            version: INDEX_VERSION,
+            repositories: entries()?,
+        })
+        .map_err(JCloneError::JsonOutput)?;
+
+        let temporary = self.path.with_extension(format!("{}.tmp", process::id()));
+        fs::write(&temporary, json + "\n").map_err(write_error)?;
+        fs::rename(&temporary, &self.path).map_err(write_error)
+    }
+
+    /// Applies `change` to the entries and saves them.
+    pub fn update(&self, change: impl FnOnce(&mut Vec<IndexEntry>)) -> JCloneResult<()> {
+        self.write_locked(|| {
+            let mut repositories = self.load()?;
+            change(&mut repositories);
+            Ok(repositories)
+        })
+    }
+
+    /// Replaces every entry, without reading the current index.
+    pub fn replace(&self, entries: Vec<IndexEntry>) -> JCloneResult<()> {
+        self.write_locked(|| Ok(entries))
+    }
+
+    /// Adds `entry`, replacing any entry for the same directory.
+    pub fn record(&self, entry: IndexEntry) -> JCloneResult<()> {
+        self.update(|repositories| {
+            repositories.retain(|existing| existing.dir != entry.dir);
+            repositories.push(entry);
+        })
+    }
+
+    /// Drops the entries for checkouts that have been removed from `dirs`.
+    pub fn forget(&self, dirs: &[&Path]) -> JCloneResult<()> {
+        self.update(|repositories| {
+            repositories.retain(|existing| !dirs.contains(&existing.dir.as_path()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    fn entry(dir: &str, cloned_at: u64) -> IndexEntry {
+        IndexEntry {
+            url: format!("git@example.com:{dir}.git"),
+            repository: Repository {
+                host: String::from("example.com"),
+                path: String::from(dir),
+            },
+            dir: PathBuf::from("/src/example.com").join(dir),
+            cloned_at,
+            variant: None,
+        }
+    }
+
+    #[test]
+    fn test_load_missing_index_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = Index::at(dir.path().join("jclone/index.json"));
+
+        assert_eq!(index.load().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_record_replaces_entry_for_same_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = Index::at(dir.path().join("jclone/index.json"));
+
+        index.record(entry("team/app", 1)).unwrap();
+        index.record(entry("team/api", 2)).unwrap();
+        index.record(entry("team/app", 3)).unwrap();
+
+        assert_eq!(
+            index.load().unwrap(),
+            vec![entry("team/api", 2), entry("team/app", 3)]
+        );
+    }
+
+    #[test]
+    fn test_forget_drops_entries_for_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = Index::at(dir.path().join("jclone/index.json"));
+
+        index.record(entry("team/app", 1)).unwrap();
+        index.record(entry("team/api", 2)).unwrap();
+        index
+            .forget(&[Path::new("/src/example.com/team/app")])
+            .unwrap();
+
+        assert_eq!(index.load().unwrap(), vec![entry("team/api", 2)]);
+    }
+
+    #[test]
+    fn test_replace_ignores_unreadable_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = Index::at(dir.path().join("index.json"));
+        fs::write(index.path(), "not json").unwrap();
+
+        assert!(index.load().is_err());
+
+        index.replace(vec![entry("team/app", 1)]).unwrap();
+
+        assert_eq!(index.load().unwrap(), vec![entry("team/app", 1)]);
+    }
+
+    #[test]
+    fn test_concurrent_records_are_all_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jclone/index.json");
+
+        thread::scope(|scope| {
+            for n in 0..8 {
+                let path = path.to_owned();
+                scope.spawn(move || {
+                    Index::at(path)
+                        .record(entry(&format!("team/app-{n}"), n))
+                        .unwrap()
+                });
+            }
+        });
+
+        let mut recorded: Vec<u64> = Index::at(path)
+            .load()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.cloned_at)
+            .collect();
+        recorded.sort();
+
+        assert_eq!(recorded, (0..8).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,412 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+use crate::configuration::Configuration;
+use crate::errors::{JCloneError, JCloneResult};
+use crate::forge;
+use crate::git;
+use crate::repository::Repository;
+use crate::ssh_config::SshConfig;
+use crate::user_configuration::{Protocol, UserConfiguration};
+
+pub fn target_dir(repo: &Repository, config: &Configuration) -> PathBuf {
+    let mut dir = PathBuf::from(&config.base_dir);
+
+    if config.use_host_dir {
+        dir.push(&repo.host)
+    }
+
+    let repo_path = PathBuf::from(&repo.path);
+    let mut components = repo_path.components().peekable();
+
+    while let Some(component) = components.next() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => (),
+            _ if !config.use_full_path && components.peek().is_some() => (),
+            _ => dir.push(component),
+        };
+    }
+
+    dir
+}
+
+/// Works out which repository `repo_str` is by the URL git will really use, after the
+/// `insteadOf` rules in git's config and, if configured, SSH host aliases. Returns that URL too.
+pub fn resolve_repository(
+    repo_str: &str,
+    base_config: &Configuration,
+) -> JCloneResult<(String, Repository)> {
+    let resolved_str = git::rewrite_url(repo_str, &git::url_rewrites(base_config)?);
+    let mut repository = match Repository::local_path(&resolved_str) {
+        Some(local_path) => Repository::from_local_path(
+            local_path,
+            &base_config.local_host,
+            &base_config.local_path_prefixes,
+        )?,
+        None => Repository::try_from(&resolved_str)?,
+    };
+
+    if base_config.resolve_ssh_aliases && Repository::protocol(&resolved_str) == Some(Protocol::Ssh)
+    {
+        let home = PathBuf::from(env::var("HOME").map_err(JCloneError::Environment)?);
+        repository.host = SshConfig::try_load(&home)?.resolve_host(&repository.host);
+    }
+
+    Ok((resolved_str, repository))
+}
+
+/// What to pass to `git clone`: the URL for the preferred protocol if `resolved_str` uses
+/// another, or otherwise `repo_str` as given. Only building a new URL when we have to keeps
+/// anything special about the original, like an SSH alias. Local paths are never converted.
+pub fn clone_str(
+    repo_str: &str,
+    resolved_str: &str,
+    repository: &Repository,
+    config: &Configuration,
+) -> String {
+    if Repository::local_path(resolved_str).is_some() {
+        return repo_str.to_owned();
+    }
+
+    // A port only applies to one protocol, and some forges use different paths for SSH and
+    // HTTPS, so a URL built from the host and path wouldn't reach the same repository
+    if Repository::has_port(resolved_str)
+        || forge::clean_path(repository, config.forge) != repository.path
+    {
+        return repo_str.to_owned();
+    }
+
+    match config.prefer_protocol {
+        Some(protocol) if Repository::protocol(resolved_str) != Some(protocol) => {
+            repository.url(protocol, &config.ssh_user)
+        }
+        _ => repo_str.to_owned(),
+    }
+}
+
+/// Where jclone would clone a repository.
+pub struct Destination {
+    /// With its path tidied for the forge.
+    pub repository: Repository,
+    /// Host of the `[[variant]]` that applies, if any.
+    pub variant: Option<String>,
+    pub target_dir: PathBuf,
+}
+
+/// Works out where cloning `repo_str` would put it, without cloning anything.
+pub fn destination(user_config: &UserConfiguration, repo_str: &str) -> JCloneResult<Destination> {
+    let base_config = Configuration::try_for_host(user_config.to_owned(), &String::new())?;
+    let (_, mut repository) = resolve_repository(repo_str, &base_config)?;
+
+    let variant = user_config
+        .variants
+        .iter()
+        .find(|variant| variant.host == repository.host)
+        .map(|variant| variant.host.to_owned());
+    let config = Configuration::try_for_host(user_config.to_owned(), &repository.host)?;
+
+    repository.path = forge::clean_path(&repository, config.forge);
+    let target_dir = target_dir(&repository, &config);
+
+    Ok(Destination {
+        repository,
+        variant,
+        target_dir,
+    })
+}
+
+/// How repositories are laid out under a base directory, for working back from a checkout to
+/// the repository it was cloned from.
+#[derive(Debug, PartialEq)]
+pub struct Layout {
+    pub base_dir: PathBuf,
+    /// The variant's host, or `None` for the top-level settings.
+    pub host: Option<String>,
+    pub use_host_dir: bool,
+}
+
+impl Layout {
+    fn new(config: &Configuration, host: Option<&str>) -> Self {
+        Self {
+            base_dir: PathBuf::from(&config.base_dir),
+            host: host.map(String::from),
+            use_host_dir: config.use_host_dir,
+        }
+    }
+
+    /// The layouts for the top-level settings and every variant.
+    pub fn try_all(user_config: &UserConfiguration) -> JCloneResult<Vec<Self>> {
+        let top_level = Configuration::try_for_host(user_config.to_owned(), &String::new())?;
+        let mut layouts = vec![Self::new(&top_level, None)];
+
+        for variant in &user_config.variants {
+            let config = Configuration::try_for_host(user_config.to_owned(), &variant.host)?;
+            layouts.push(Self::new(&config, Some(&variant.host)));
+        }
+
+        Ok(layouts)
+    }
+}
+
+/// Works out the host and path of the repository checked out at `relative` inside a base
+/// directory used by `layouts`. The host is `None` when the layout doesn't record it.
+pub fn repository_for(layouts: &[&Layout], relative: &Path) -> (Option<String>, String) {
+    let components: Vec<String> = relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+
+    let in_host_dir = |layout: &&&Layout| layout.use_host_dir && components.len() > 1;
+
+    // A variant's own host directory wins, then the top-level settings' host directories
+    let host_dir_layout = layouts
+        .iter()
+        .filter(in_host_dir)
+        .find(|layout| layout.host.as_ref() == components.first())
+        .or_else(|| {
+            layouts
+                .iter()
+                .filter(in_host_dir)
+                .find(|layout| layout.host.is_none())
+        });
+
+    if host_dir_layout.is_some() {
+        return (Some(components[0].to_owned()), components[1..].join("/"));
+    }
+
+    let host = layouts
+        .iter()
+        .filter(|layout| !layout.use_host_dir)
+        .find_map(|layout| layout.host.to_owned());
+
+    (host, components.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::{fixture, rstest};
+
+    use crate::user_configuration::{Forge, OutputStyle};
+
+    use super::*;
+
+    #[fixture]
+    fn default_repo() -> Repository {
+        Repository {
+            host: String::from("example.com"),
+            path: String::from("some/big/long/path"),
+        }
+    }
+
+    #[fixture]
+    fn default_config() -> Configuration {
+        Configuration {
+            base_dir: String::from("/home/ferris/src"),
+            use_host_dir: true,
+            use_full_path: true,
+            output_style: OutputStyle::default(),
+            git_executable: String::from("git"),
+            prefer_protocol: None,
+            ssh_user: String::from("git"),
+            user_name: None,
+            user_email: None,
+            signing_key: None,
+            post_clone: vec![],
+            allowed_hosts: vec![],
+            denied_hosts: vec![],
+            pre_clone: None,
+            ssh_key: None,
+            ssh_options: vec![],
+            env: Default::default(),
+            persist_ssh_command: false,
+            resolve_ssh_aliases: false,
+            checkout_linked_ref: false,
+            forge: Forge::Auto,
+            local_host: String::from("local"),
+            local_path_prefixes: vec![],
+        }
+    }
+
+    #[rstest]
+    #[case::https_to_ssh("https://example.com/team/app.git", "git@example.com:team/app.git")]
+    #[case::already_ssh("example:team/app", "example:team/app")]
+    #[case::local_path("/mnt/mirrors/team/app.git", "/mnt/mirrors/team/app.git")]
+    #[case::file_url("file:///mnt/mirrors/team/app.git", "file:///mnt/mirrors/team/app.git")]
+    #[case::https_port(
+        "https://example.com:8443/team/app.git",
+        "https://example.com:8443/team/app.git"
+    )]
+    #[case::azure(
+        "https://dev.azure.com/org/project/_git/app",
+        "https://dev.azure.com/org/project/_git/app"
+    )]
+    #[case::bitbucket_server(
+        "https://bitbucket.corp/scm/team/app.git",
+        "https://bitbucket.corp/scm/team/app.git"
+    )]
+    fn test_clone_str_with_prefer_protocol_ssh(
+        default_config: Configuration,
+        #[case] repo_str: &str,
+        #[case] expected: &str,
+    ) {
+        let config = Configuration {
+            prefer_protocol: Some(Protocol::Ssh),
+            ..default_config
+        };
+        let repository = Repository::try_from(&repo_str.to_owned()).unwrap();
+
+        assert_eq!(
+            clone_str(repo_str, repo_str, &repository, &config),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::ssh_to_https("git@example.com:team/app.git", "https://example.com/team/app.git")]
+    #[case::ssh_port(
+        "ssh://git@bitbucket.corp:7999/team/app.git",
+        "ssh://git@bitbucket.corp:7999/team/app.git"
+    )]
+    #[case::gerrit_port("ssh://review.corp:29418/team/app", "ssh://review.corp:29418/team/app")]
+    #[case::azure(
+        "git@ssh.dev.azure.com:v3/org/project/app",
+        "git@ssh.dev.azure.com:v3/org/project/app"
+    )]
+    fn test_clone_str_with_prefer_protocol_https(
+        default_config: Configuration,
+        #[case] repo_str: &str,
+        #[case] expected: &str,
+    ) {
+        let config = Configuration {
+            prefer_protocol: Some(Protocol::Https),
+            ..default_config
+        };
+        let repository = Repository::try_from(&repo_str.to_owned()).unwrap();
+
+        assert_eq!(
+            clone_str(repo_str, repo_str, &repository, &config),
+            expected
+        );
+    }
+
+    #[rstest]
+    fn test_target_dir_returns_whole_path_given_default_config(
+        default_repo: Repository,
+        default_config: Configuration,
+    ) {
+        assert_eq!(
+            target_dir(&default_repo, &default_config),
+            PathBuf::from("/home/ferris/src/example.com/some/big/long/path")
+        )
+    }
+
+    #[rstest]
+    fn test_target_dir_skips_host_dir_when_use_host_dir_is_false(
+        default_repo: Repository,
+        default_config: Configuration,
+    ) {
+        let config = Configuration {
+            use_host_dir: false,
+            ..default_config
+        };
+
+        assert_eq!(
+            target_dir(&default_repo, &config),
+            PathBuf::from("/home/ferris/src/some/big/long/path")
+        )
+    }
+
+    #[rstest]
+    fn test_target_dir_ensures_repo_path_is_relative(
+        default_repo: Repository,
+        default_config: Configuration,
+    ) {
+        let repo = Repository {
+            path: String::from("/some/absolute/path"),
+            ..default_repo
+        };
+
+        assert_eq!(
+            target_dir(&repo, &default_config),
+            PathBuf::from("/home/ferris/src/example.com/some/absolute/path")
+        )
+    }
+
+    #[rstest]
+    fn test_target_dir_uses_only_project_name_when_use_full_path_is_false(
+        default_repo: Repository,
+        default_config: Configuration,
+    ) {
+        let config = Configuration {
+            use_full_path: false,
+            ..default_config
+        };
+
+        assert_eq!(
+            target_dir(&default_repo, &config),
+            PathBuf::from("/home/ferris/src/example.com/path")
+        )
+    }
+
+    fn layout(base_dir: &str, host: Option<&str>, use_host_dir: bool) -> Layout {
+        Layout {
+            base_dir: PathBuf::from(base_dir),
+            host: host.map(String::from),
+            use_host_dir,
+        }
+    }
+
+    #[rstest]
+    #[case::top_level_host_dir(
+        vec![layout("/src", None, true)],
+        "github.com/rust-lang/rustlings",
+        Some("github.com"),
+        "rust-lang/rustlings"
+    )]
+    #[case::top_level_without_host_dir(vec![layout("/src", None, false)], "rust-lang/rustlings", None, "rust-lang/rustlings")]
+    #[case::variant_without_host_dir(
+        vec![layout("/work", Some("git.example.com"), false)],
+        "team/app",
+        Some("git.example.com"),
+        "team/app"
+    )]
+    #[case::variant_host_dir_wins(
+        vec![layout("/src", None, false), layout("/src", Some("git.example.com"), true)],
+        "git.example.com/team/app",
+        Some("git.example.com"),
+        "team/app"
+    )]
+    #[case::too_short_for_host_dir(vec![layout("/src", None, true)], "app", None, "app")]
+    fn test_repository_for(
+        #[case] layouts: Vec<Layout>,
+        #[case] relative: &str,
+        #[case] expected_host: Option<&str>,
+        #[case] expected_path: &str,
+    ) {
+        let layouts: Vec<&Layout> = layouts.iter().collect();
+
+        assert_eq!(
+            repository_for(&layouts, Path::new(relative)),
+            (expected_host.map(String::from), String::from(expected_path))
+        );
+    }
+
+    #[test]
+    fn test_repository_for_inverts_target_dir() {
+        let config = default_config();
+        let repository = default_repo();
+        let layout = Layout::new(&config, None);
+        let target_dir = target_dir(&repository, &config);
+
+        assert_eq!(
+            repository_for(
+                &[&layout],
+                target_dir.strip_prefix(&layout.base_dir).unwrap()
+            ),
+            (Some(repository.host), repository.path)
+        );
+    }
+}
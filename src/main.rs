@@ -1,20 +1,33 @@
 use std::env;
-use std::path::Component;
-use std::path::PathBuf;
 use std::process::ExitCode;
 use thiserror::Error;
 
+use crate::cli::{Command, USAGE};
 use crate::configuration::Configuration;
 use crate::errors::JCloneError;
+use crate::forge::Linked;
 use crate::git::Git;
-use crate::repository::Repository;
-use crate::user_configuration::OutputStyle;
+use crate::hooks::HookVariables;
+use crate::index::{Index, IndexEntry};
+use crate::user_configuration::{OutputStyle, UserConfiguration};
 
+mod cli;
+mod commands;
 mod configuration;
 mod errors;
+mod forge;
+mod fuzzy;
 mod git;
+mod hooks;
+mod index;
+mod layout;
+mod migration;
+mod pattern;
+mod policy;
 mod repository;
+mod ssh_config;
 mod user_configuration;
+mod workspace;
 
 #[derive(Error, Debug)]
 #[error(transparent)]
@@ -35,133 +48,215 @@ fn main() -> ExitCode {
 }
 
 fn jclone() -> Result<(), HandledError> {
-    let repo_str = env::args().nth(1).ok_or_else(|| {
-        HandledError::Unreported(JCloneError::Generic("missing argument: repository"))
-    })?;
-
-    let repository = Repository::try_from(&repo_str).map_err(HandledError::Unreported)?;
-    let config = Configuration::try_load(&repository.host).map_err(HandledError::Unreported)?;
-    let git = Git::new(&repo_str, &config);
-    let target_dir = target_dir(&repository, &config);
-
-    git.clone(&target_dir)
-        .map_err(|err| match config.output_style {
-            OutputStyle::GitOnly | OutputStyle::Quiet => HandledError::Reported(err),
-            _ => HandledError::Unreported(err),
-        })?;
-
-    match config.output_style {
-        OutputStyle::Default | OutputStyle::NoGit => println!("🎉 Done!"),
-        _ => (),
+    match Command::parse(env::args().skip(1))? {
+        Command::Clone { repo_str, dry_run } => clone(&repo_str, dry_run),
+        Command::Config(command) => Ok(commands::config::run(command)?),
+        Command::List { filter, format } => Ok(commands::list::run(filter, format)?),
+        Command::Status { filter, json } => Ok(commands::status::run(filter, json)?),
+        Command::Sync { filter, pull, jobs } => Ok(commands::sync::run(filter, pull, jobs)?),
+        Command::Exec {
+            filter,
+            jobs,
+            command,
+        } => Ok(commands::exec::run(filter, jobs, command)?),
+        Command::Reorganise { yes, dirs } => Ok(commands::reorganise::run(yes, dirs)?),
+        Command::Adopt {
+            dirs,
+            recursive,
+            symlink,
+        } => Ok(commands::adopt::run(dirs, recursive, symlink)?),
+        Command::Rm { query, force } => Ok(commands::rm::run(query, force)?),
+        Command::Prune {
+            filter,
+            older_than,
+            archive,
+            yes,
+        } => Ok(commands::prune::run(filter, older_than, archive, yes)?),
+        Command::Path { query } => Ok(commands::path::run(query)?),
+        Command::Index(command) => Ok(commands::index::run(command)?),
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    }
+}
+
+fn clone(link_str: &String, dry_run: bool) -> Result<(), HandledError> {
+    let user_config = UserConfiguration::try_load()?;
+
+    // Clone the repository a web page belongs to, remembering what the page pointed at
+    let (repo_str, linked) = match forge::parse_web_link(link_str) {
+        Some(web_link) => (web_link.repo_url, web_link.linked),
+        None => (link_str.to_owned(), None),
     };
+    let repo_str = &repo_str;
 
-    Ok(())
-}
+    let base_config = Configuration::try_for_host(user_config.to_owned(), &String::new())?;
 
-fn target_dir(repo: &Repository, config: &Configuration) -> PathBuf {
-    let mut dir = PathBuf::from(&config.base_dir);
+    // Lay out and configure by the URL git will really use, but leave git to resolve it itself
+    let (resolved_str, mut repository) = layout::resolve_repository(repo_str, &base_config)?;
 
-    if config.use_host_dir {
-        dir.push(&repo.host)
-    }
+    let variant = user_config
+        .variants
+        .iter()
+        .find(|variant| variant.host == repository.host)
+        .map(|variant| variant.host.to_owned());
+    let config = Configuration::try_for_host(user_config, &repository.host)?;
 
-    let repo_path = PathBuf::from(&repo.path);
-    let mut components = repo_path.components().peekable();
+    let clone_str = layout::clone_str(repo_str, &resolved_str, &repository, &config);
 
-    while let Some(component) = components.next() {
-        match component {
-            Component::Prefix(_) | Component::RootDir => (),
-            _ if !config.use_full_path && components.peek().is_some() => (),
-            _ => dir.push(component),
-        };
-    }
+    // Only tidy the path for the forge now, as building a URL above needs the original
+    repository.path = forge::clean_path(&repository, config.forge);
 
-    dir
-}
+    let git = Git::new(&clone_str, &config);
+    let target_dir = layout::target_dir(&repository, &config);
+    let hook_variables = HookVariables {
+        path: &target_dir,
+        host: &repository.host,
+        name: repository.name(),
+    };
+
+    policy::check_host(&repository.host, &config)?;
+
+    let pull_request = match &linked {
+        Some(Linked::PullRequest { number, head_ref }) => {
+            Some((format!("pr-{number}"), head_ref.to_owned()))
+        }
+        _ => None,
+    };
 
-#[cfg(test)]
-mod tests {
-    use rstest::{fixture, rstest};
+    let linked_ref = match linked {
+        Some(Linked::Ref(components)) if config.checkout_linked_ref => Some(components),
+        _ => None,
+    };
 
-    use crate::user_configuration::OutputStyle;
+    // Reviewing another pull request in a repository we've already cloned only needs a fetch
+    let already_cloned = pull_request.is_some() && target_dir.join(".git").exists();
 
-    use super::*;
+    if dry_run {
+        if repo_str != link_str {
+            println!("🔍 {link_str} is a web page in {repo_str}");
+        }
 
-    #[fixture]
-    fn default_repo() -> Repository {
-        Repository {
-            host: String::from("example.com"),
-            path: String::from("some/big/long/path"),
+        if let Some(command) = config.pre_clone.as_ref().filter(|_| !already_cloned) {
+            println!("🔍 Would run pre_clone check {command}");
+        }
+
+        if resolved_str != *repo_str {
+            println!("🔍 Your git config rewrites {repo_str} to {resolved_str}");
+        }
+
+        if clone_str != *repo_str {
+            println!("🔍 Would use {clone_str} for your preferred protocol");
+        }
+
+        match already_cloned {
+            true => println!("🔍 {} is already cloned", target_dir.display()),
+            false => println!("🔍 Would clone {clone_str} into {}", target_dir.display()),
+        }
+
+        if let Some(components) = &linked_ref {
+            println!(
+                "🔍 Would check out a branch, tag or commit from {}",
+                components.join("/")
+            );
         }
-    }
 
-    #[fixture]
-    fn default_config() -> Configuration {
-        Configuration {
-            base_dir: String::from("/home/ferris/src"),
-            use_host_dir: true,
-            use_full_path: true,
-            output_style: OutputStyle::default(),
-            git_executable: String::from("git"),
+        if let Some((branch, head_ref)) = &pull_request {
+            println!("🔍 Would fetch {head_ref} into {branch} and check it out");
         }
+
+        if already_cloned {
+            return Ok(());
+        }
+
+        for (key, value) in config.local_git_config() {
+            println!("🔍 Would set {key} to {value}");
+        }
+
+        for command in &config.post_clone {
+            println!("🔍 Would run {}", hooks::expand(command, &hook_variables));
+        }
+
+        return Ok(());
     }
 
-    #[rstest]
-    fn test_target_dir_returns_whole_path_given_default_config(
-        default_repo: Repository,
-        default_config: Configuration,
-    ) {
-        assert_eq!(
-            target_dir(&default_repo, &default_config),
-            PathBuf::from("/home/ferris/src/example.com/some/big/long/path")
-        )
+    let handle_git_error = |err| match config.output_style {
+        OutputStyle::GitOnly | OutputStyle::Quiet => HandledError::Reported(err),
+        _ => HandledError::Unreported(err),
+    };
+
+    let print_flavour = matches!(
+        config.output_style,
+        OutputStyle::Default | OutputStyle::NoGit
+    );
+
+    if already_cloned {
+        if print_flavour {
+            println!("📂 {} is already cloned", target_dir.display());
+        }
+    } else {
+        if let Some(command) = &config.pre_clone {
+            hooks::run_pre_clone(command, &repository, &config.output_style)?;
+        }
+
+        if print_flavour && clone_str != *repo_str {
+            println!("🔀 Using {clone_str}");
+        }
+
+        git.clone(&target_dir).map_err(handle_git_error)?;
+
+        Index::update_default(|index| {
+            index.record(IndexEntry {
+                url: clone_str.to_owned(),
+                repository: repository.to_owned(),
+                dir: target_dir.to_owned(),
+                cloned_at: index::now(),
+                variant,
+            })
+        });
+
+        if let Some(components) = &linked_ref {
+            let checked_out = git
+                .checkout_linked_ref(&target_dir, components)
+                .map_err(handle_git_error)?;
+
+            match checked_out {
+                Some(name) if print_flavour => println!("🌿 Checked out {name}"),
+                None if print_flavour => println!(
+                    "⚠️ Couldn't find a branch, tag or commit in {}, so staying on the default branch",
+                    components.join("/")
+                ),
+                _ => (),
+            }
+        }
+
+        for (key, value) in config.local_git_config() {
+            git.set_config(&target_dir, key, &value)
+                .map_err(handle_git_error)?;
+
+            if print_flavour {
+                println!("🪪 Set {key} to {value}");
+            }
+        }
     }
 
-    #[rstest]
-    fn test_target_dir_skips_host_dir_when_use_host_dir_is_false(
-        default_repo: Repository,
-        default_config: Configuration,
-    ) {
-        let config = Configuration {
-            use_host_dir: false,
-            ..default_config
-        };
-
-        assert_eq!(
-            target_dir(&default_repo, &config),
-            PathBuf::from("/home/ferris/src/some/big/long/path")
-        )
+    if let Some((branch, head_ref)) = &pull_request {
+        git.fetch_pull_request(&target_dir, head_ref, branch)
+            .map_err(handle_git_error)?;
+
+        if print_flavour {
+            println!("🌿 Checked out {head_ref} as {branch}");
+        }
     }
 
-    #[rstest]
-    fn test_target_dir_ensures_repo_path_is_relative(
-        default_repo: Repository,
-        default_config: Configuration,
-    ) {
-        let repo = Repository {
-            path: String::from("/some/absolute/path"),
-            ..default_repo
-        };
-
-        assert_eq!(
-            target_dir(&repo, &default_config),
-            PathBuf::from("/home/ferris/src/example.com/some/absolute/path")
-        )
+    if !already_cloned {
+        hooks::run_post_clone(&config.post_clone, &hook_variables, &config.output_style)?;
     }
 
-    #[rstest]
-    fn test_target_dir_uses_only_project_name_when_use_full_path_is_false(
-        default_repo: Repository,
-        default_config: Configuration,
-    ) {
-        let config = Configuration {
-            use_full_path: false,
-            ..default_config
-        };
-
-        assert_eq!(
-            target_dir(&default_repo, &config),
-            PathBuf::from("/home/ferris/src/example.com/path")
-        )
+    if print_flavour {
+        println!("🎉 Done!");
     }
+
+    Ok(())
 }
@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use toml_edit::{DocumentMut, value};
+
+use crate::errors::{JCloneError, JCloneResult};
+
+/// The config format this version of jclone reads. Files without a `version` key were written
+/// before versioning was introduced and are read as version 1.
+pub const CURRENT_VERSION: i64 = 1;
+
+/// Upgrades a document by one version, returning any deprecation warnings for the user.
+type Migration = fn(&mut DocumentMut) -> Vec<String>;
+
+/// `MIGRATIONS[n]` upgrades a document from version `n + 1` to `n + 2`.
+const MIGRATIONS: [Migration; (CURRENT_VERSION - 1) as usize] = [];
+
+pub struct Migrated {
+    pub document: DocumentMut,
+    pub warnings: Vec<String>,
+}
+
+fn document_version(document: &DocumentMut) -> i64 {
+    // Anything other than an integer is left for deserialization to reject
+    document
+        .get("version")
+        .and_then(|version| version.as_integer())
+        .unwrap_or(1)
+}
+
+fn migrate_with(
+    mut document: DocumentMut,
+    config_path: &Path,
+    migrations: &[Migration],
+) -> JCloneResult<Migrated> {
+    let latest = migrations.len() as i64 + 1;
+    let version = document_version(&document);
+
+    if !(1..=latest).contains(&version) {
+        return Err(JCloneError::ConfigurationVersion {
+            path: config_path.to_owned(),
+            version,
+            latest,
+        });
+    }
+
+    let warnings: Vec<String> = migrations[(version - 1) as usize..]
+        .iter()
+        .flat_map(|migration| migration(&mut document))
+        .collect();
+
+    if version < latest {
+        document["version"] = value(latest);
+    }
+
+    Ok(Migrated { document, warnings })
+}
+
+/// Parses a config file and upgrades it to `CURRENT_VERSION`, keeping comments and formatting.
+pub fn migrate(config_str: &str, config_path: &Path) -> JCloneResult<Migrated> {
+    let document = config_str
+        .parse::<DocumentMut>()
+        .map_err(|err| JCloneError::ConfigurationDocument(config_path.to_owned(), err))?;
+
+    migrate_with(document, config_path, &MIGRATIONS)
+}
+
+/// Records the version explicitly so the file keeps being read the same way by later releases.
+pub fn stamp_version(document: &mut DocumentMut) {
+    if !document.contains_key("version") {
+        document["version"] = value(CURRENT_VERSION);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_output_to_verbosity(document: &mut DocumentMut) -> Vec<String> {
+        match document.remove("output") {
+            Some(output) => {
+                document["verbosity"] = output;
+                vec![String::from("`output` is deprecated, use `verbosity`")]
+            }
+            None => vec![],
+        }
+    }
+
+    #[test]
+    fn test_migrate_with_upgrades_old_version_and_keeps_comments() {
+        let config_str = r#"# my config
+base_dir = "/base/dir" # where things go
+output = "quiet"
+"#;
+        let document = config_str.parse().unwrap();
+
+        let migrated = migrate_with(
+            document,
+            Path::new(".jclone.toml"),
+            &[rename_output_to_verbosity],
+        )
+        .unwrap();
+
+        assert_eq!(
+            migrated.document.to_string(),
+            r#"# my config
+base_dir = "/base/dir" # where things go
+verbosity = "quiet"
+version = 2
+"#
+        );
+        assert_eq!(
+            migrated.warnings,
+            vec!["`output` is deprecated, use `verbosity`"]
+        );
+    }
+
+    #[test]
+    fn test_migrate_with_leaves_current_version_alone() {
+        let config_str = "version = 2\noutput = \"quiet\"\n";
+        let document = config_str.parse().unwrap();
+
+        let migrated = migrate_with(
+            document,
+            Path::new(".jclone.toml"),
+            &[rename_output_to_verbosity],
+        )
+        .unwrap();
+
+        assert_eq!(migrated.document.to_string(), config_str);
+        assert!(migrated.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_migrate_with_rejects_newer_version() {
+        let document = "version = 3\n".parse().unwrap();
+
+        let result = migrate_with(
+            document,
+            Path::new(".jclone.toml"),
+            &[rename_output_to_verbosity],
+        );
+
+        assert!(matches!(
+            result,
+            Err(JCloneError::ConfigurationVersion {
+                version: 3,
+                latest: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_migrate_reads_unversioned_file_as_version_1() {
+        let document: DocumentMut = "base_dir = \"/base/dir\"\n".parse().unwrap();
+
+        assert_eq!(document_version(&document), 1);
+
+        let migrated = migrate_with(
+            document,
+            Path::new(".jclone.toml"),
+            &[rename_output_to_verbosity],
+        )
+        .unwrap();
+
+        assert_eq!(
+            migrated.document.to_string(),
+            "base_dir = \"/base/dir\"\nversion = 2\n"
+        );
+    }
+
+    #[test]
+    fn test_stamp_version_keeps_comments() {
+        let mut document: DocumentMut =
+            "# my config\nbase_dir = \"/base/dir\"\n\n[[variant]]\nhost = \"example.com\"\n"
+                .parse()
+                .unwrap();
+
+        stamp_version(&mut document);
+
+        assert_eq!(
+            document.to_string(),
+            format!(
+                "# my config\nbase_dir = \"/base/dir\"\nversion = {CURRENT_VERSION}\n\n[[variant]]\nhost = \"example.com\"\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,70 @@
+/// Matches `text` against a glob-style `pattern`, where `*` matches any run of characters
+/// (including none) and `?` matches exactly one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Where to resume if the most recent `*` needs to swallow another character
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    backtrack = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Matches a host against a glob-style pattern, ignoring case.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    glob_match(&pattern.to_ascii_lowercase(), &host.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::exact("github.com", "github.com", true)]
+    #[case::different("github.com", "gitlab.com", false)]
+    #[case::star_prefix("*.example.com", "git.example.com", true)]
+    #[case::star_does_not_match_bare_domain("*.example.com", "example.com", false)]
+    #[case::star_anywhere("git*.com", "github.com", true)]
+    #[case::star_empty("github*", "github", true)]
+    #[case::only_star("*", "anything.at.all", true)]
+    #[case::question_mark("git?ab.com", "gitlab.com", true)]
+    #[case::question_mark_needs_character("github.co?", "github.co", false)]
+    #[case::backtracking("*a*b", "xaxxab", true)]
+    #[case::trailing_text("github.com", "github.com.evil", false)]
+    fn test_glob_match(#[case] pattern: &str, #[case] text: &str, #[case] expected: bool) {
+        assert_eq!(glob_match(pattern, text), expected);
+    }
+
+    #[test]
+    fn test_host_matches_ignores_case() {
+        assert!(host_matches("*.Example.COM", "Git.example.com"));
+    }
+}
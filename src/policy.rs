@@ -0,0 +1,80 @@
+use crate::configuration::Configuration;
+use crate::errors::{JCloneError, JCloneResult};
+use crate::pattern::host_matches;
+
+/// Checks a host against `denied_hosts` and, if it's set, `allowed_hosts`. Denial wins.
+pub fn check_host(host: &str, config: &Configuration) -> JCloneResult<()> {
+    if let Some(pattern) = config
+        .denied_hosts
+        .iter()
+        .find(|pattern| host_matches(pattern, host))
+    {
+        return Err(JCloneError::HostDenied {
+            host: host.to_owned(),
+            pattern: pattern.to_owned(),
+        });
+    }
+
+    match config.allowed_hosts.is_empty()
+        || config
+            .allowed_hosts
+            .iter()
+            .any(|pattern| host_matches(pattern, host))
+    {
+        true => Ok(()),
+        false => Err(JCloneError::HostNotAllowed(host.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::user_configuration::UserConfiguration;
+
+    use super::*;
+
+    fn config(allowed_hosts: &[&str], denied_hosts: &[&str]) -> Configuration {
+        let user_config = UserConfiguration {
+            allowed_hosts: Some(allowed_hosts.iter().map(|p| p.to_string()).collect()),
+            denied_hosts: Some(denied_hosts.iter().map(|p| p.to_string()).collect()),
+            ..UserConfiguration::default()
+        };
+
+        Configuration::from_user_configuration(
+            user_config,
+            &String::from("example.com"),
+            String::from("/home/ferris"),
+        )
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case::no_lists(&[], &[], "github.com")]
+    #[case::allowed(&["github.com", "*.example.com"], &[], "git.example.com")]
+    #[case::not_denied(&[], &["*.evil.com"], "github.com")]
+    fn test_check_host_allows(
+        #[case] allowed_hosts: &[&str],
+        #[case] denied_hosts: &[&str],
+        #[case] host: &str,
+    ) {
+        assert!(check_host(host, &config(allowed_hosts, denied_hosts)).is_ok());
+    }
+
+    #[test]
+    fn test_check_host_refuses_host_not_in_allowed_hosts() {
+        let result = check_host("gitlab.com", &config(&["github.com"], &[]));
+
+        assert!(matches!(result, Err(JCloneError::HostNotAllowed(host)) if host == "gitlab.com"));
+    }
+
+    #[test]
+    fn test_check_host_denial_wins_over_allowed_hosts() {
+        let result = check_host("gist.github.com", &config(&["*"], &["gist.*"]));
+
+        assert!(matches!(
+            result,
+            Err(JCloneError::HostDenied { pattern, .. }) if pattern == "gist.*"
+        ));
+    }
+}
@@ -1,30 +1,178 @@
+use std::path::{self, Component, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
 use crate::errors::{JCloneError, JCloneResult};
+use crate::user_configuration::Protocol;
+
+/// Host for local paths and `file://` URLs when the config doesn't set `local_host`.
+pub const DEFAULT_LOCAL_HOST: &str = "local";
 
-#[derive(Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Repository {
     pub host: String,
     pub path: String,
 }
 
+impl Repository {
+    /// How git will reach the remote. SSH can be an `ssh://` URL or the scp-like `host:path`
+    /// syntax. `None` for anything else, e.g. `git://` or a local path.
+    pub fn protocol(repo_str: &str) -> Option<Protocol> {
+        if Self::local_path(repo_str).is_some() {
+            return None;
+        }
+
+        match repo_str.split_once("://") {
+            Some(("ssh" | "git+ssh" | "ssh+git", _)) | None => Some(Protocol::Ssh),
+            Some(("https" | "http", _)) => Some(Protocol::Https),
+            Some(_) => None,
+        }
+    }
+
+    /// Whether a URL names a port, e.g. `ssh://git@example.com:7999/team/app.git`. The scp-like
+    /// `host:path` syntax can't.
+    pub fn has_port(repo_str: &str) -> bool {
+        let Some((_, part_after_proto)) = repo_str.split_once("://") else {
+            return false;
+        };
+
+        let authority = part_after_proto.split('/').next().unwrap_or_default();
+        let host_and_port = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+
+        matches!(
+            host_and_port.split_once(':'),
+            Some((_, port)) if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit())
+        )
+    }
+
+    /// The path on disk for a local path or `file://` URL. Like git, anything without a colon, or
+    /// with a slash before the first one, is a path. `None` for remote repositories.
+    pub fn local_path(repo_str: &str) -> Option<&str> {
+        if let Some(path) = repo_str.strip_prefix("file://") {
+            return Some(path);
+        }
+
+        if repo_str.is_empty() || repo_str.contains("://") {
+            return None;
+        }
+
+        match repo_str.split_once(':') {
+            Some((prefix, _)) if !prefix.contains('/') => None,
+            _ => Some(repo_str),
+        }
+    }
+
+    /// A repository on disk, filed under `host` with its absolute path (less the first of
+    /// `strip_prefixes` that matches) as the repository path.
+    pub fn from_local_path(
+        local_path: &str,
+        host: &str,
+        strip_prefixes: &[String],
+    ) -> JCloneResult<Self> {
+        let absolute = path::absolute(local_path).map_err(JCloneError::CurrentDirectory)?;
+
+        // Resolve `..` without touching the filesystem, so symlinks in the prefixes still match
+        let mut normalised = PathBuf::new();
+
+        for component in absolute.components() {
+            match component {
+                Component::ParentDir => {
+                    normalised.pop();
+                }
+                Component::CurDir => (),
+                _ => normalised.push(component),
+            }
+        }
+
+        let relative = strip_prefixes
+            .iter()
+            .find_map(|prefix| normalised.strip_prefix(prefix).ok())
+            .unwrap_or(&normalised);
+
+        let path_str = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let path = path_str.strip_suffix(".git").unwrap_or(&path_str);
+
+        match path.trim_end_matches('/') {
+            "" => Err(JCloneError::RepositoryParse("unexpected format")),
+            path => Ok(Repository {
+                host: host.to_owned(),
+                path: path.to_owned(),
+            }),
+        }
+    }
+
+    /// A URL for this repository using `protocol`. `ssh_user` may be empty.
+    pub fn url(&self, protocol: Protocol, ssh_user: &str) -> String {
+        match (protocol, ssh_user) {
+            (Protocol::Ssh, "") => format!("{}:{}.git", self.host, self.path),
+            (Protocol::Ssh, user) => format!("{user}@{}:{}.git", self.host, self.path),
+            (Protocol::Https, _) => format!("https://{}/{}.git", self.host, self.path),
+        }
+    }
+
+    /// The last component of the path, e.g. `rustlings` for `rust-lang/rustlings`.
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+/// Splits scp-like `[user@]host:path` syntax into the part before and after the colon.
+fn split_scp_like(repo_str: &str) -> JCloneResult<(&str, &str)> {
+    match repo_str.split_once(':') {
+        Some(("", _)) | Some((_, "")) | None => {
+            Err(JCloneError::RepositoryParse("unexpected format"))
+        }
+        Some(parts) => Ok(parts),
+    }
+}
+
+/// Splits the part of a URL after `scheme://` into `[user@]host` and path, dropping any port.
+/// Also accepts scp-like syntax after the scheme, e.g. `ssh://git@example.com:my_repo`.
+fn split_url(part_after_proto: &str) -> JCloneResult<(&str, &str)> {
+    let (authority, path) = part_after_proto
+        .split_once('/')
+        .unwrap_or((part_after_proto, ""));
+
+    let host_and_port = match authority.rsplit_once('@') {
+        None => authority,
+        Some((_, host)) => host,
+    };
+
+    let authority = match host_and_port.split_once(':') {
+        None => authority,
+        Some((_, port)) if port.chars().all(|c| c.is_ascii_digit()) => {
+            &authority[..authority.len() - port.len() - 1]
+        }
+        Some(_) => return split_scp_like(part_after_proto),
+    };
+
+    match (authority, path.trim_end_matches('/')) {
+        ("", _) | (_, "") => Err(JCloneError::RepositoryParse("unexpected format")),
+        parts => Ok(parts),
+    }
+}
+
 impl TryFrom<&String> for Repository {
     type Error = JCloneError;
 
     fn try_from(repo_str: &String) -> JCloneResult<Self> {
-        let part_after_proto = match repo_str.split_once("://") {
-            None => repo_str,
-            Some((_, p)) => p,
-        };
+        if let Some(local_path) = Repository::local_path(repo_str) {
+            return Repository::from_local_path(local_path, DEFAULT_LOCAL_HOST, &[]);
+        }
 
-        let (prefix, suffix) = match part_after_proto.split_once(':') {
-            Some(("", _)) | Some((_, "")) | None => {
-                return Err(JCloneError::RepositoryParse("unexpected format"));
-            }
-            Some((prefix, _)) if prefix.contains('/') => {
-                return Err(JCloneError::RepositoryParse(
-                    "local paths are not supported",
-                ));
-            }
-            Some(parts) => parts,
+        let (prefix, suffix) = match repo_str.split_once("://") {
+            None => split_scp_like(repo_str)?,
+            Some((_, part_after_proto)) => split_url(part_after_proto)?,
         };
 
         let host_part = match prefix.split_once('@') {
@@ -58,6 +206,14 @@ mod tests {
         assert!(Repository::try_from(&repo_str).is_err());
     }
 
+    #[rstest]
+    #[case::url_without_path("https://example.com")]
+    #[case::url_without_host("https:///my_repo")]
+    #[case::root_dir("/")]
+    fn test_unsupported_repo_string_error(#[case] repo_str: String) {
+        assert!(Repository::try_from(&repo_str).is_err());
+    }
+
     #[rstest]
     #[case::ssh_compact("example.com:my_repo", "example.com", "my_repo")]
     #[case::ssh_user("git@example.com:my_repo", "example.com", "my_repo")]
@@ -75,6 +231,34 @@ mod tests {
         "my_user/my_repo"
     )]
     #[case::git_scheme_extension("git://git@example.com:my_repo.git", "example.com", "my_repo")]
+    #[case::https_url(
+        "https://example.com/my_user/my_repo",
+        "example.com",
+        "my_user/my_repo"
+    )]
+    #[case::https_url_extension_trailing_slash(
+        "https://example.com/my_user/my_repo.git/",
+        "example.com",
+        "my_user/my_repo"
+    )]
+    #[case::https_url_user_and_port(
+        "https://ferris@example.com:8443/my_user/my_repo.git",
+        "example.com",
+        "my_user/my_repo"
+    )]
+    #[case::ssh_url_port(
+        "ssh://git@example.com:2222/my_user/my_repo.git",
+        "example.com",
+        "my_user/my_repo"
+    )]
+    #[case::local_path("/mnt/mirrors/team/app.git", "local", "mnt/mirrors/team/app")]
+    #[case::local_path_with_colon(
+        "/home/ferris/my_repo:branch",
+        "local",
+        "home/ferris/my_repo:branch"
+    )]
+    #[case::local_path_parent_dir("/mnt/mirrors/../team/app/.git", "local", "mnt/team/app")]
+    #[case::file_url("file:///mnt/mirrors/team/app.git", "local", "mnt/mirrors/team/app")]
     fn test_repo_string_correctly_converted_to_repository(
         #[case] input: String,
         #[case] host: String,
@@ -85,4 +269,79 @@ mod tests {
         assert_eq!(Repository::try_from(&input)?, expected);
         Ok(())
     }
+
+    #[rstest]
+    #[case::single_component("my_repo", "my_repo")]
+    #[case::long_path("my_user/my_group/my_repo", "my_repo")]
+    fn test_name_is_last_path_component(#[case] path: String, #[case] expected: &str) {
+        let repository = Repository {
+            host: String::from("example.com"),
+            path,
+        };
+
+        assert_eq!(repository.name(), expected);
+    }
+
+    #[rstest]
+    #[case::scp_like("git@example.com:my_repo", Some(Protocol::Ssh))]
+    #[case::ssh_scheme("ssh://git@example.com:my_repo", Some(Protocol::Ssh))]
+    #[case::git_scheme("git://example.com:my_repo", None)]
+    #[case::https_scheme("https://example.com/my_repo", Some(Protocol::Https))]
+    #[case::local_path("/mnt/mirrors/my_repo", None)]
+    #[case::file_scheme("file:///mnt/mirrors/my_repo", None)]
+    fn test_protocol(#[case] repo_str: &str, #[case] expected: Option<Protocol>) {
+        assert_eq!(Repository::protocol(repo_str), expected);
+    }
+
+    #[rstest]
+    #[case::ssh_port("ssh://git@example.com:7999/my_repo", true)]
+    #[case::https_port("https://example.com:8443/my_repo", true)]
+    #[case::no_port("https://example.com/my_repo", false)]
+    #[case::scp_like("git@example.com:my_repo", false)]
+    #[case::scp_like_after_scheme("ssh://git@example.com:my_repo", false)]
+    fn test_has_port(#[case] repo_str: &str, #[case] expected: bool) {
+        assert_eq!(Repository::has_port(repo_str), expected);
+    }
+
+    #[rstest]
+    #[case::ssh(Protocol::Ssh, "git", "git@example.com:my_user/my_repo.git")]
+    #[case::ssh_without_user(Protocol::Ssh, "", "example.com:my_user/my_repo.git")]
+    #[case::https(Protocol::Https, "git", "https://example.com/my_user/my_repo.git")]
+    fn test_url(#[case] protocol: Protocol, #[case] ssh_user: &str, #[case] expected: &str) {
+        let repository = Repository {
+            host: String::from("example.com"),
+            path: String::from("my_user/my_repo"),
+        };
+
+        assert_eq!(repository.url(protocol, ssh_user), expected);
+    }
+
+    #[rstest]
+    #[case::first_matching_prefix(&["/srv", "/mnt/mirrors", "/mnt"], "team/app")]
+    #[case::whole_components_only(&["/mnt/mirror"], "mnt/mirrors/team/app")]
+    #[case::no_prefixes(&[], "mnt/mirrors/team/app")]
+    fn test_from_local_path_strips_prefix(#[case] prefixes: &[&str], #[case] expected: &str) {
+        let prefixes: Vec<String> = prefixes.iter().map(|p| p.to_string()).collect();
+
+        let repository =
+            Repository::from_local_path("/mnt/mirrors/team/app.git", "mirrors", &prefixes).unwrap();
+
+        assert_eq!(
+            repository,
+            Repository {
+                host: String::from("mirrors"),
+                path: String::from(expected),
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::absolute("/mnt/my_repo", Some("/mnt/my_repo"))]
+    #[case::relative("../my_repo", Some("../my_repo"))]
+    #[case::file_url("file:///mnt/my_repo", Some("/mnt/my_repo"))]
+    #[case::scp_like("git@example.com:my_repo", None)]
+    #[case::url("https://example.com/my_repo", None)]
+    fn test_local_path(#[case] repo_str: &str, #[case] expected: Option<&str>) {
+        assert_eq!(Repository::local_path(repo_str), expected);
+    }
 }
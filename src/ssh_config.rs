@@ -0,0 +1,269 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::{JCloneError, JCloneResult};
+use crate::pattern::{glob_match, host_matches};
+
+/// The same limit OpenSSH uses, which also stops `Include` loops.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+struct HostBlock {
+    patterns: Vec<String>,
+    hostname: Option<String>,
+}
+
+impl HostBlock {
+    fn new(patterns: Vec<String>) -> Self {
+        Self {
+            patterns,
+            hostname: None,
+        }
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        let (negated, patterns): (Vec<&String>, Vec<&String>) = self
+            .patterns
+            .iter()
+            .partition(|pattern| pattern.starts_with('!'));
+
+        patterns.iter().any(|pattern| host_matches(pattern, host))
+            && !negated
+                .iter()
+                .any(|pattern| host_matches(&pattern[1..], host))
+    }
+}
+
+/// Just enough of an OpenSSH client config to map `Host` aliases to their `HostName`.
+pub struct SshConfig {
+    blocks: Vec<HostBlock>,
+}
+
+fn split_arguments(arguments: &str) -> Vec<String> {
+    arguments
+        .split_whitespace()
+        .map(|argument| argument.trim_matches('"').to_owned())
+        .collect()
+}
+
+fn include_paths(pattern: &str, ssh_dir: &Path, home: &Path) -> Vec<PathBuf> {
+    let path = match pattern.strip_prefix("~/") {
+        Some(rest) => home.join(rest),
+        None => ssh_dir.join(pattern),
+    };
+
+    let file_pattern = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    if !file_pattern.contains(['*', '?']) {
+        return vec![path];
+    }
+
+    let mut paths: Vec<PathBuf> = path
+        .parent()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| glob_match(&file_pattern, &entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .collect();
+
+    paths.sort();
+    paths
+}
+
+impl SshConfig {
+    /// Loads `$HOME/.ssh/config`, or an empty config if there isn't one.
+    pub fn try_load(home: &Path) -> JCloneResult<Self> {
+        let mut config = Self { blocks: vec![] };
+        config.load_file(
+            &home.join(".ssh").join("config"),
+            home,
+            0,
+            vec![String::from("*")],
+        )?;
+        Ok(config)
+    }
+
+    fn load_file(
+        &mut self,
+        path: &Path,
+        home: &Path,
+        depth: usize,
+        patterns: Vec<String>,
+    ) -> JCloneResult<()> {
+        if depth > MAX_INCLUDE_DEPTH || !path.is_file() {
+            return Ok(());
+        }
+
+        let text = fs::read_to_string(path)
+            .map_err(|err| JCloneError::SshConfigLoad(path.to_owned(), err))?;
+
+        self.parse(&text, home, depth, patterns)
+    }
+
+    /// Parses config text into host blocks. Anything before the first `Host` line belongs to
+    /// `patterns`: every host for the main file, or the enclosing block for an included one.
+    fn parse(
+        &mut self,
+        text: &str,
+        home: &Path,
+        depth: usize,
+        patterns: Vec<String>,
+    ) -> JCloneResult<()> {
+        let ssh_dir = home.join(".ssh");
+
+        let mut current_patterns = patterns;
+        self.blocks
+            .push(HostBlock::new(current_patterns.to_owned()));
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, arguments) = line
+                .split_once(|c: char| c.is_whitespace() || c == '=')
+                .unwrap_or((line, ""));
+            let arguments = arguments.trim().trim_start_matches('=').trim();
+
+            match keyword.to_ascii_lowercase().as_str() {
+                "host" => {
+                    current_patterns = split_arguments(arguments);
+                    self.blocks
+                        .push(HostBlock::new(current_patterns.to_owned()));
+                }
+                // Match conditions aren't supported, so nothing in a Match block applies
+                "match" => {
+                    current_patterns = vec![];
+                    self.blocks.push(HostBlock::new(vec![]));
+                }
+                "hostname" => {
+                    if let Some(block) = self.blocks.last_mut()
+                        && block.hostname.is_none()
+                    {
+                        block.hostname = Some(arguments.trim_matches('"').to_owned());
+                    }
+                }
+                "include" => {
+                    for pattern in split_arguments(arguments) {
+                        for path in include_paths(&pattern, &ssh_dir, home) {
+                            self.load_file(&path, home, depth + 1, current_patterns.to_owned())?;
+                        }
+                    }
+
+                    // Carry on with the block we were in before the Include
+                    self.blocks
+                        .push(HostBlock::new(current_patterns.to_owned()));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The real hostname for `host`, or `host` itself if no `HostName` applies.
+    pub fn resolve_host(&self, host: &str) -> String {
+        self.blocks
+            .iter()
+            .filter(|block| block.matches(host))
+            .find_map(|block| block.hostname.as_ref())
+            .map_or_else(
+                || host.to_owned(),
+                |hostname| hostname.replace("%h", host).replace("%%", "%"),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    const SSH_CONFIG: &str = r#"
+# Work GitHub account
+Host work-gh
+    HostName github.com
+    IdentityFile ~/.ssh/id_work
+
+Host gitlab gl
+    HostName=gitlab.example.com
+
+Host *.internal !secret.internal
+    HostName %h.example.com
+
+Host work-gh
+    HostName ignored.example.com
+
+Match host anything
+    HostName match.example.com
+"#;
+
+    fn parse(text: &str) -> SshConfig {
+        let mut config = SshConfig { blocks: vec![] };
+        config
+            .parse(text, Path::new("/home/ferris"), 0, vec![String::from("*")])
+            .unwrap();
+        config
+    }
+
+    #[rstest]
+    #[case::alias("work-gh", "github.com")]
+    #[case::second_pattern_with_equals("gl", "gitlab.example.com")]
+    #[case::wildcard_with_token("git.internal", "git.internal.example.com")]
+    #[case::negated_pattern("secret.internal", "secret.internal")]
+    #[case::not_configured("github.com", "github.com")]
+    #[case::match_block_ignored("anything", "anything")]
+    fn test_resolve_host(#[case] host: &str, #[case] expected: &str) {
+        assert_eq!(parse(SSH_CONFIG).resolve_host(host), expected);
+    }
+
+    #[test]
+    fn test_try_load_follows_includes() {
+        let home = tempfile::tempdir().unwrap();
+        let ssh_dir = home.path().join(".ssh");
+        fs::create_dir_all(ssh_dir.join("config.d")).unwrap();
+
+        fs::write(
+            ssh_dir.join("config"),
+            "Include config.d/*.conf\nHost personal\n    HostName gitlab.com\nHost nested\n    Include nested.conf\n",
+        )
+        .unwrap();
+        fs::write(
+            ssh_dir.join("config.d/work.conf"),
+            "Host work-gh\n    HostName github.com\n",
+        )
+        .unwrap();
+        fs::write(
+            ssh_dir.join("config.d/ignored.txt"),
+            "Host personal\n    HostName ignored.example.com\n",
+        )
+        .unwrap();
+
+        fs::write(
+            ssh_dir.join("nested.conf"),
+            "    HostName nested.example.com\n",
+        )
+        .unwrap();
+
+        let config = SshConfig::try_load(home.path()).unwrap();
+
+        assert_eq!(config.resolve_host("work-gh"), "github.com");
+        assert_eq!(config.resolve_host("nested"), "nested.example.com");
+        assert_eq!(config.resolve_host("other"), "other");
+        assert_eq!(config.resolve_host("personal"), "gitlab.com");
+    }
+
+    #[test]
+    fn test_try_load_without_ssh_config() {
+        let home = tempfile::tempdir().unwrap();
+
+        let config = SshConfig::try_load(home.path()).unwrap();
+
+        assert_eq!(config.resolve_host("work-gh"), "work-gh");
+    }
+}
@@ -1,42 +1,152 @@
-use serde::Deserialize;
-use std::{env, fs, path::PathBuf};
-
-use crate::errors::{JCloneError, JCloneResult};
-
-#[derive(Deserialize, Debug, PartialEq, Default, Clone)]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    errors::{JCloneError, JCloneResult},
+    migration,
+};
+
+/// What jclone prints to the terminal.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, PartialEq, Default, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub enum OutputStyle {
+    /// Clone progress, git errors, jclone errors and "Done!"
     #[default]
     Default,
+    /// Only git's own output
     GitOnly,
+    /// Only jclone's output
     NoGit,
+    /// Nothing except jclone errors like config parsing issues
     Quiet,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Default)]
+/// Transport for git to use when cloning.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// e.g. `git@github.com:rust-lang/rustlings.git`
+    Ssh,
+    /// e.g. `https://github.com/rust-lang/rustlings.git`
+    Https,
+}
+
+/// How a forge lays out repository URLs, used to turn them into a clean path to clone into.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, PartialEq, Default, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Forge {
+    /// Recognise Azure DevOps and Bitbucket Server URLs by their shape
+    #[default]
+    Auto,
+    /// e.g. `https://dev.azure.com/org/project/_git/repo` or
+    /// `git@ssh.dev.azure.com:v3/org/project/repo`
+    Azure,
+    /// e.g. `ssh://git@bitbucket.example.com:7999/scm/proj/repo.git`
+    BitbucketServer,
+    /// e.g. `https://gerrit.example.com/a/project`
+    Gerrit,
+    /// Use the path from the URL as it is
+    Generic,
+}
+
+/// jclone configuration, read from `$HOME/.jclone.toml` and any `.jclone.toml` between there
+/// and the current directory.
+#[derive(Deserialize, JsonSchema, Debug, PartialEq, Default, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct UserConfiguration {
+    /// Version of the config format. Files without one are read as version 1.
+    pub version: Option<u32>,
+    /// Directory to clone into. Defaults to `$HOME/src`.
     pub base_dir: Option<String>,
+    /// Resolve a relative `base_dir` against the directory containing this file.
+    pub relative_base_dir: Option<bool>,
+    /// Include the repository host as a directory. Defaults to `true`.
     pub use_host_dir: Option<bool>,
+    /// Use the full repository path rather than just its name. Defaults to `true`.
     pub use_full_path: Option<bool>,
     #[serde(rename = "output")]
     pub output_style: Option<OutputStyle>,
+    /// The git executable to run. Defaults to `git`.
     pub git_executable: Option<String>,
+    /// Convert repository URLs to this protocol before cloning.
+    pub prefer_protocol: Option<Protocol>,
+    /// User for SSH URLs created by `prefer_protocol`. Defaults to `git`.
+    pub ssh_user: Option<String>,
+    /// Shell commands to run in the new checkout after cloning. `{path}`, `{host}` and `{name}`
+    /// are replaced with the checkout directory, repository host and repository name.
+    pub post_clone: Option<Vec<String>>,
+    /// Host patterns that may be cloned from, e.g. `*.example.com`. If set, anything else is
+    /// refused.
+    pub allowed_hosts: Option<Vec<String>>,
+    /// Host patterns that may never be cloned from. These win over `allowed_hosts`.
+    pub denied_hosts: Option<Vec<String>>,
+    /// Shell command to run before cloning, given the parsed repository as JSON on stdin. A
+    /// non-zero exit status refuses the clone.
+    pub pre_clone: Option<String>,
+    /// Map SSH host aliases from `~/.ssh/config` to their real `HostName` when choosing where
+    /// to clone and which variant applies.
+    pub resolve_ssh_aliases: Option<bool>,
+    /// After cloning from a web link to a branch, tag or commit, check it out.
+    pub checkout_linked_ref: Option<bool>,
+    /// Host to use for local paths and `file://` URLs. Defaults to `local`.
+    pub local_host: Option<String>,
+    /// Leading directories to remove from local paths, e.g. `/mnt/mirrors`. The first that
+    /// matches is used.
+    pub local_path_prefixes: Option<Vec<String>>,
+    /// Settings for a particular host, overriding the ones above.
     #[serde(default)]
     #[serde(rename = "variant")]
     pub variants: Vec<UserHostConfiguration>,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Default, Clone)]
+/// Settings for a particular host. The first variant matching a repository's host is used.
+#[derive(Deserialize, JsonSchema, Debug, PartialEq, Default, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct UserHostConfiguration {
+    /// Host to match exactly, e.g. `github.com`.
     pub host: String,
+    /// Directory to clone into.
     pub base_dir: Option<String>,
+    /// Include the repository host as a directory.
     pub use_host_dir: Option<bool>,
+    /// Use the full repository path rather than just its name.
     pub use_full_path: Option<bool>,
     #[serde(rename = "output")]
     pub output_style: Option<OutputStyle>,
+    /// The git executable to run.
     pub git_executable: Option<String>,
+    /// Convert repository URLs to this protocol before cloning.
+    pub prefer_protocol: Option<Protocol>,
+    /// User for SSH URLs created by `prefer_protocol`.
+    pub ssh_user: Option<String>,
+    /// `user.name` to set in the new repository's local git config.
+    pub user_name: Option<String>,
+    /// `user.email` to set in the new repository's local git config.
+    pub user_email: Option<String>,
+    /// `user.signingKey` to set in the new repository's local git config.
+    pub signing_key: Option<String>,
+    /// Shell commands to run in the new checkout after cloning, replacing the top-level ones.
+    pub post_clone: Option<Vec<String>>,
+    /// SSH private key for git to use, e.g. `~/.ssh/id_work`.
+    pub ssh_key: Option<String>,
+    /// Extra `-o` options for SSH, e.g. `Port=2222`.
+    pub ssh_options: Option<Vec<String>>,
+    /// Environment variables to set for git. These override the SSH command built from
+    /// `ssh_key` and `ssh_options`.
+    pub env: Option<BTreeMap<String, String>>,
+    /// Save the SSH command as `core.sshCommand` in the new repository, so later git commands
+    /// use it too.
+    pub persist_ssh_command: Option<bool>,
+    /// After cloning from a web link to a branch, tag or commit, check it out.
+    pub checkout_linked_ref: Option<bool>,
+    /// The kind of forge the host runs, for tidying up its repository paths. Defaults to
+    /// `auto`.
+    pub forge: Option<Forge>,
 }
 
 impl TryFrom<String> for UserConfiguration {
@@ -47,6 +157,141 @@ impl TryFrom<String> for UserConfiguration {
     }
 }
 
+pub const CONFIG_FILE_NAME: &str = ".jclone.toml";
+
+/// Lists the config files that apply in `cwd`, from lowest to highest precedence: the user
+/// config in `home` first, then any found in directories between `home` and `cwd`, nearest last.
+/// Directories outside `home` are never searched, and neither are git work trees, as their
+/// files come from whoever wrote the repository.
+fn config_paths(home: &Path, cwd: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<&Path> = match cwd.strip_prefix(home) {
+        Ok(_) => cwd.ancestors().take_while(|dir| *dir != home).collect(),
+        Err(_) => vec![],
+    };
+
+    dirs.reverse();
+
+    let mut directory_paths: Vec<PathBuf> = dirs
+        .into_iter()
+        .take_while(|dir| !dir.join(".git").exists())
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .collect();
+
+    let mut paths = vec![home.join(CONFIG_FILE_NAME)];
+    paths.append(&mut directory_paths);
+    paths.retain(|path| path.is_file());
+    paths
+}
+
+fn resolve_base_dir(base_dir: Option<String>, config_dir: &Path) -> Option<String> {
+    base_dir.map(|dir| match Path::new(&dir).is_relative() {
+        true => config_dir.join(dir).to_string_lossy().into_owned(),
+        false => dir,
+    })
+}
+
+impl UserHostConfiguration {
+    /// The settings this variant sets, other than its host.
+    pub fn set_keys(&self) -> Vec<&'static str> {
+        [
+            ("base_dir", self.base_dir.is_some()),
+            ("use_host_dir", self.use_host_dir.is_some()),
+            ("use_full_path", self.use_full_path.is_some()),
+            ("output", self.output_style.is_some()),
+            ("git_executable", self.git_executable.is_some()),
+            ("prefer_protocol", self.prefer_protocol.is_some()),
+            ("ssh_user", self.ssh_user.is_some()),
+            ("user_name", self.user_name.is_some()),
+            ("user_email", self.user_email.is_some()),
+            ("signing_key", self.signing_key.is_some()),
+            ("post_clone", self.post_clone.is_some()),
+            ("ssh_key", self.ssh_key.is_some()),
+            ("ssh_options", self.ssh_options.is_some()),
+            ("env", self.env.is_some()),
+            ("persist_ssh_command", self.persist_ssh_command.is_some()),
+            ("checkout_linked_ref", self.checkout_linked_ref.is_some()),
+            ("forge", self.forge.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(key, is_set)| is_set.then_some(key))
+        .collect()
+    }
+
+    /// Layers `overlay` on top of this variant. Values set in `overlay` win.
+    fn merge(self, overlay: Self) -> Self {
+        Self {
+            host: overlay.host,
+            base_dir: overlay.base_dir.or(self.base_dir),
+            use_host_dir: overlay.use_host_dir.or(self.use_host_dir),
+            use_full_path: overlay.use_full_path.or(self.use_full_path),
+            output_style: overlay.output_style.or(self.output_style),
+            git_executable: overlay.git_executable.or(self.git_executable),
+            prefer_protocol: overlay.prefer_protocol.or(self.prefer_protocol),
+            ssh_user: overlay.ssh_user.or(self.ssh_user),
+            user_name: overlay.user_name.or(self.user_name),
+            user_email: overlay.user_email.or(self.user_email),
+            signing_key: overlay.signing_key.or(self.signing_key),
+            post_clone: overlay.post_clone.or(self.post_clone),
+            ssh_key: overlay.ssh_key.or(self.ssh_key),
+            ssh_options: overlay.ssh_options.or(self.ssh_options),
+            env: overlay.env.or(self.env),
+            persist_ssh_command: overlay.persist_ssh_command.or(self.persist_ssh_command),
+            checkout_linked_ref: overlay.checkout_linked_ref.or(self.checkout_linked_ref),
+            forge: overlay.forge.or(self.forge),
+        }
+    }
+}
+
+/// Puts the `overlay` variants before the `base` ones. The first variant for a host in
+/// `overlay` is filled in from the first for it in `base`, which then has nothing left to match.
+fn merge_variants(
+    mut base: Vec<UserHostConfiguration>,
+    overlay: Vec<UserHostConfiguration>,
+) -> Vec<UserHostConfiguration> {
+    let mut merged: Vec<UserHostConfiguration> = vec![];
+
+    for variant in overlay {
+        let first_for_host = !merged.iter().any(|other| other.host == variant.host);
+
+        match base.iter().position(|other| other.host == variant.host) {
+            Some(index) if first_for_host => merged.push(base.remove(index).merge(variant)),
+            _ => merged.push(variant),
+        }
+    }
+
+    merged.append(&mut base);
+    merged
+}
+
+/// Loads the config files in `paths`, ignoring settings that run commands in any but the user
+/// config at `user_config_path`.
+fn load_layers(
+    user_config_path: &Path,
+    paths: Vec<PathBuf>,
+) -> JCloneResult<Vec<(PathBuf, UserConfiguration)>> {
+    let mut layers = vec![];
+
+    for path in paths {
+        let mut config = UserConfiguration::try_load_file(&path)?;
+
+        if path != user_config_path {
+            for key in config.user_only_keys() {
+                eprintln!(
+                    "⚠️  {}: ignoring {key}, which is only read from {}",
+                    path.display(),
+                    user_config_path.display()
+                );
+            }
+
+            config = config.without_user_only_keys();
+        }
+
+        layers.push((path, config));
+    }
+
+    Ok(layers)
+}
+
 impl UserConfiguration {
     pub fn variant_matching_host(&self, host: &String) -> UserHostConfiguration {
         self.variants
@@ -55,24 +300,159 @@ impl UserConfiguration {
             .map_or_else(UserHostConfiguration::default, |v| v.to_owned())
     }
 
-    pub fn try_load() -> JCloneResult<Self> {
-        let home = PathBuf::from(env::var("HOME").map_err(JCloneError::Environment)?);
-        let config_path = PathBuf::from(&home).join(".jclone.toml");
+    /// Layers `overlay` on top of this config. Values set in `overlay` win, and its variants are
+    /// matched before any of ours, filled in from ours for the same host.
+    pub fn merge(self, overlay: Self) -> Self {
+        Self {
+            version: overlay.version.or(self.version),
+            base_dir: overlay.base_dir.or(self.base_dir),
+            relative_base_dir: overlay.relative_base_dir.or(self.relative_base_dir),
+            use_host_dir: overlay.use_host_dir.or(self.use_host_dir),
+            use_full_path: overlay.use_full_path.or(self.use_full_path),
+            output_style: overlay.output_style.or(self.output_style),
+            git_executable: overlay.git_executable.or(self.git_executable),
+            prefer_protocol: overlay.prefer_protocol.or(self.prefer_protocol),
+            ssh_user: overlay.ssh_user.or(self.ssh_user),
+            post_clone: overlay.post_clone.or(self.post_clone),
+            allowed_hosts: overlay.allowed_hosts.or(self.allowed_hosts),
+            denied_hosts: overlay.denied_hosts.or(self.denied_hosts),
+            pre_clone: overlay.pre_clone.or(self.pre_clone),
+            resolve_ssh_aliases: overlay.resolve_ssh_aliases.or(self.resolve_ssh_aliases),
+            checkout_linked_ref: overlay.checkout_linked_ref.or(self.checkout_linked_ref),
+            local_host: overlay.local_host.or(self.local_host),
+            local_path_prefixes: overlay.local_path_prefixes.or(self.local_path_prefixes),
+            variants: merge_variants(self.variants, overlay.variants),
+        }
+    }
 
-        if !config_path.exists() {
-            return Ok(Self::default());
+    /// The settings that run commands or restrict where jclone clones from, and so are only read
+    /// from the user config, if set here.
+    pub fn user_only_keys(&self) -> Vec<&'static str> {
+        let top_level_keys = [
+            ("git_executable", self.git_executable.is_some()),
+            ("post_clone", self.post_clone.is_some()),
+            ("allowed_hosts", self.allowed_hosts.is_some()),
+            ("denied_hosts", self.denied_hosts.is_some()),
+            ("pre_clone", self.pre_clone.is_some()),
+        ];
+
+        let mut keys: Vec<&'static str> = top_level_keys
+            .into_iter()
+            .filter_map(|(key, is_set)| is_set.then_some(key))
+            .collect();
+
+        for variant in &self.variants {
+            let variant_keys = [
+                ("git_executable", variant.git_executable.is_some()),
+                ("post_clone", variant.post_clone.is_some()),
+                ("ssh_key", variant.ssh_key.is_some()),
+                ("ssh_options", variant.ssh_options.is_some()),
+                ("env", variant.env.is_some()),
+            ];
+
+            for (key, is_set) in variant_keys {
+                if is_set && !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
         }
 
-        match fs::read_to_string(&config_path) {
-            Ok(config_str) => Ok(toml::from_str(&config_str)
-                .map_err(|err| JCloneError::ConfigurationParse(config_path, err))?),
-            Err(err) => Err(JCloneError::ConfigurationFileLoad(config_path, err)),
+        keys
+    }
+
+    /// Drops the settings only the user config may set, for a directory-scoped config file.
+    pub fn without_user_only_keys(mut self) -> Self {
+        self.git_executable = None;
+        self.post_clone = None;
+        self.allowed_hosts = None;
+        self.denied_hosts = None;
+        self.pre_clone = None;
+
+        for variant in self.variants.iter_mut() {
+            variant.git_executable = None;
+            variant.post_clone = None;
+            variant.ssh_key = None;
+            variant.ssh_options = None;
+            variant.env = None;
+        }
+
+        self
+    }
+
+    /// With `relative_base_dir = true`, relative `base_dir` values are taken to be relative to
+    /// the directory containing the config file rather than wherever jclone is run from.
+    fn resolve_relative_base_dirs(mut self, config_dir: &Path) -> Self {
+        if self.relative_base_dir != Some(true) {
+            return self;
+        }
+
+        self.base_dir = resolve_base_dir(self.base_dir, config_dir);
+
+        for variant in self.variants.iter_mut() {
+            variant.base_dir = resolve_base_dir(variant.base_dir.take(), config_dir);
+        }
+
+        self
+    }
+
+    pub fn try_load_file(config_path: &Path) -> JCloneResult<Self> {
+        let config_str = fs::read_to_string(config_path)
+            .map_err(|err| JCloneError::ConfigurationFileLoad(config_path.to_owned(), err))?;
+
+        let migrated = migration::migrate(&config_str, config_path)?;
+
+        for warning in migrated.warnings {
+            eprintln!("⚠️  {}: {warning}", config_path.display());
         }
+
+        let config: Self = toml::from_str(&migrated.document.to_string())
+            .map_err(|err| JCloneError::ConfigurationParse(config_path.to_owned(), err))?;
+
+        let config_dir = config_path.parent().unwrap_or(Path::new("/"));
+        Ok(config.resolve_relative_base_dirs(config_dir))
+    }
+
+    pub fn user_config_path() -> JCloneResult<PathBuf> {
+        let home = PathBuf::from(env::var("HOME").map_err(JCloneError::Environment)?);
+        Ok(home.join(CONFIG_FILE_NAME))
+    }
+
+    /// Lists the config files that apply in the current directory, lowest precedence first.
+    pub fn try_config_paths() -> JCloneResult<Vec<PathBuf>> {
+        Self::try_config_paths_in(&env::current_dir().map_err(JCloneError::CurrentDirectory)?)
+    }
+
+    /// Lists the config files that apply in `dir`, lowest precedence first.
+    pub fn try_config_paths_in(dir: &Path) -> JCloneResult<Vec<PathBuf>> {
+        let home = PathBuf::from(env::var("HOME").map_err(JCloneError::Environment)?);
+
+        Ok(config_paths(&home, dir))
+    }
+
+    /// Loads each config file that applies in the current directory, lowest precedence first.
+    pub fn try_load_layers() -> JCloneResult<Vec<(PathBuf, Self)>> {
+        load_layers(&Self::user_config_path()?, Self::try_config_paths()?)
+    }
+
+    /// JSON Schema describing the config file format, for editors that support schema-driven
+    /// completion.
+    pub fn json_schema() -> String {
+        let schema = schemars::schema_for!(UserConfiguration);
+
+        format!("{:#}\n", schema.as_value())
+    }
+
+    pub fn try_load() -> JCloneResult<Self> {
+        Ok(Self::try_load_layers()?
+            .into_iter()
+            .fold(Self::default(), |config, (_, layer)| config.merge(layer)))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::configuration::Configuration;
+
     use super::*;
 
     #[test]
@@ -81,11 +461,23 @@ mod tests {
         let actual = UserConfiguration::try_from(user_config_str).unwrap();
 
         let default_config = UserConfiguration {
+            version: None,
             base_dir: None,
+            relative_base_dir: None,
             use_host_dir: None,
             use_full_path: None,
             output_style: None,
             git_executable: None,
+            prefer_protocol: None,
+            ssh_user: None,
+            post_clone: None,
+            allowed_hosts: None,
+            denied_hosts: None,
+            pre_clone: None,
+            resolve_ssh_aliases: None,
+            checkout_linked_ref: None,
+            local_host: None,
+            local_path_prefixes: None,
             variants: vec![],
         };
 
@@ -105,11 +497,23 @@ mod tests {
         );
 
         let expected = UserConfiguration {
+            version: None,
             base_dir: Some(String::from("/base/dir")),
+            relative_base_dir: None,
             use_host_dir: Some(false),
             use_full_path: Some(true),
             output_style: Some(OutputStyle::NoGit),
             git_executable: Some(String::from("/home/ferris/bin/git")),
+            prefer_protocol: None,
+            ssh_user: None,
+            post_clone: None,
+            allowed_hosts: None,
+            denied_hosts: None,
+            pre_clone: None,
+            resolve_ssh_aliases: None,
+            checkout_linked_ref: None,
+            local_host: None,
+            local_path_prefixes: None,
             variants: vec![],
         };
 
@@ -127,6 +531,13 @@ mod tests {
             use_full_path = true
             output = "git-only"
             git_executable = "/home/ferris/bin/git"
+            prefer_protocol = "ssh"
+            ssh_user = "ferris"
+            post_clone = ["direnv allow"]
+            allowed_hosts = ["github.com", "*.example.com"]
+            denied_hosts = ["gist.github.com"]
+            pre_clone = "policy-check"
+            resolve_ssh_aliases = true
 
             [[variant]]
             host = "example.com"
@@ -135,6 +546,17 @@ mod tests {
             use_full_path = true
             output = "default"
             git_executable = "bin/git-example-com"
+            prefer_protocol = "https"
+            user_name = "Ferris"
+            user_email = "ferris@example.com"
+            signing_key = "ABCD1234"
+            post_clone = ["pre-commit install", "direnv allow"]
+            ssh_key = "~/.ssh/id_example"
+            ssh_options = ["Port=2222"]
+            env = { GIT_TRACE = "1" }
+            persist_ssh_command = true
+            checkout_linked_ref = true
+            forge = "bitbucket-server"
 
             [[variant]]
             host = "example.net"
@@ -147,11 +569,26 @@ mod tests {
         );
 
         let expected = UserConfiguration {
+            version: None,
             base_dir: Some(String::from("/base/dir")),
+            relative_base_dir: None,
             use_host_dir: Some(false),
             use_full_path: Some(true),
             output_style: Some(OutputStyle::GitOnly),
             git_executable: Some(String::from("/home/ferris/bin/git")),
+            prefer_protocol: Some(Protocol::Ssh),
+            ssh_user: Some(String::from("ferris")),
+            post_clone: Some(vec![String::from("direnv allow")]),
+            allowed_hosts: Some(vec![
+                String::from("github.com"),
+                String::from("*.example.com"),
+            ]),
+            denied_hosts: Some(vec![String::from("gist.github.com")]),
+            pre_clone: Some(String::from("policy-check")),
+            resolve_ssh_aliases: Some(true),
+            checkout_linked_ref: None,
+            local_host: None,
+            local_path_prefixes: None,
             variants: vec![
                 UserHostConfiguration {
                     host: String::from("example.com"),
@@ -160,6 +597,24 @@ mod tests {
                     use_full_path: Some(true),
                     output_style: Some(OutputStyle::Default),
                     git_executable: Some(String::from("bin/git-example-com")),
+                    prefer_protocol: Some(Protocol::Https),
+                    ssh_user: None,
+                    user_name: Some(String::from("Ferris")),
+                    user_email: Some(String::from("ferris@example.com")),
+                    signing_key: Some(String::from("ABCD1234")),
+                    post_clone: Some(vec![
+                        String::from("pre-commit install"),
+                        String::from("direnv allow"),
+                    ]),
+                    ssh_key: Some(String::from("~/.ssh/id_example")),
+                    ssh_options: Some(vec![String::from("Port=2222")]),
+                    env: Some(BTreeMap::from([(
+                        String::from("GIT_TRACE"),
+                        String::from("1"),
+                    )])),
+                    persist_ssh_command: Some(true),
+                    checkout_linked_ref: Some(true),
+                    forge: Some(Forge::BitbucketServer),
                 },
                 UserHostConfiguration {
                     host: String::from("example.net"),
@@ -168,6 +623,7 @@ mod tests {
                     use_full_path: Some(false),
                     output_style: Some(OutputStyle::Quiet),
                     git_executable: Some(String::from("bin/git-example-net")),
+                    ..UserHostConfiguration::default()
                 },
             ],
         };
@@ -244,4 +700,316 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_merge_prefers_overlay_values_and_variants() {
+        let base = UserConfiguration {
+            base_dir: Some(String::from("/base/dir")),
+            use_host_dir: Some(false),
+            variants: vec![UserHostConfiguration {
+                host: String::from("example.com"),
+                base_dir: Some(String::from("/base/example-com")),
+                ..UserHostConfiguration::default()
+            }],
+            ..UserConfiguration::default()
+        };
+
+        let overlay = UserConfiguration {
+            base_dir: Some(String::from("/overlay/dir")),
+            variants: vec![UserHostConfiguration {
+                host: String::from("example.com"),
+                base_dir: Some(String::from("/overlay/example-com")),
+                ..UserHostConfiguration::default()
+            }],
+            ..UserConfiguration::default()
+        };
+
+        let actual = base.merge(overlay);
+
+        assert_eq!(actual.base_dir, Some(String::from("/overlay/dir")));
+        assert_eq!(actual.use_host_dir, Some(false));
+        assert_eq!(
+            actual
+                .variant_matching_host(&String::from("example.com"))
+                .base_dir,
+            Some(String::from("/overlay/example-com"))
+        );
+    }
+
+    #[test]
+    fn test_merge_fills_in_variants_for_the_same_host() {
+        let base = UserConfiguration {
+            variants: vec![UserHostConfiguration {
+                host: String::from("example.com"),
+                ssh_key: Some(String::from("~/.ssh/id_example")),
+                user_email: Some(String::from("ferris@example.com")),
+                ..UserHostConfiguration::default()
+            }],
+            ..UserConfiguration::default()
+        };
+
+        let overlay = UserConfiguration {
+            variants: vec![UserHostConfiguration {
+                host: String::from("example.com"),
+                user_email: Some(String::from("ferris@client-a.example.com")),
+                ..UserHostConfiguration::default()
+            }],
+            ..UserConfiguration::default()
+        };
+
+        let actual = base.merge(overlay);
+
+        assert_eq!(
+            actual.variants,
+            vec![UserHostConfiguration {
+                host: String::from("example.com"),
+                ssh_key: Some(String::from("~/.ssh/id_example")),
+                user_email: Some(String::from("ferris@client-a.example.com")),
+                ..UserHostConfiguration::default()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_resolve_relative_base_dirs_when_enabled() {
+        let user_config = UserConfiguration {
+            base_dir: Some(String::from("repos")),
+            relative_base_dir: Some(true),
+            variants: vec![
+                UserHostConfiguration {
+                    host: String::from("example.com"),
+                    base_dir: Some(String::from("example-com")),
+                    ..UserHostConfiguration::default()
+                },
+                UserHostConfiguration {
+                    host: String::from("example.net"),
+                    base_dir: Some(String::from("/absolute/dir")),
+                    ..UserHostConfiguration::default()
+                },
+            ],
+            ..UserConfiguration::default()
+        };
+
+        let actual = user_config.resolve_relative_base_dirs(Path::new("/home/ferris/work"));
+
+        assert_eq!(
+            actual.base_dir,
+            Some(String::from("/home/ferris/work/repos"))
+        );
+        assert_eq!(
+            actual.variants[0].base_dir,
+            Some(String::from("/home/ferris/work/example-com"))
+        );
+        assert_eq!(
+            actual.variants[1].base_dir,
+            Some(String::from("/absolute/dir"))
+        );
+    }
+
+    #[test]
+    fn test_resolve_relative_base_dirs_when_disabled() {
+        let user_config = UserConfiguration {
+            base_dir: Some(String::from("repos")),
+            ..UserConfiguration::default()
+        };
+
+        let actual = user_config.resolve_relative_base_dirs(Path::new("/home/ferris/work"));
+
+        assert_eq!(actual.base_dir, Some(String::from("repos")));
+    }
+
+    #[test]
+    fn test_config_paths_finds_files_between_home_and_cwd() {
+        let home = tempfile::tempdir().unwrap();
+        let cwd = home.path().join("work/client-a/project");
+        fs::create_dir_all(&cwd).unwrap();
+
+        for dir in ["", "work", "work/client-a"] {
+            fs::write(home.path().join(dir).join(CONFIG_FILE_NAME), "").unwrap();
+        }
+
+        let expected = vec![
+            home.path().join(CONFIG_FILE_NAME),
+            home.path().join("work").join(CONFIG_FILE_NAME),
+            home.path().join("work/client-a").join(CONFIG_FILE_NAME),
+        ];
+
+        assert_eq!(config_paths(home.path(), &cwd), expected);
+    }
+
+    #[test]
+    fn test_config_paths_ignores_directories_outside_home() {
+        let root = tempfile::tempdir().unwrap();
+        let home = root.path().join("home");
+        let cwd = root.path().join("elsewhere");
+        fs::create_dir_all(&home).unwrap();
+        fs::create_dir_all(&cwd).unwrap();
+        fs::write(root.path().join(CONFIG_FILE_NAME), "").unwrap();
+        fs::write(cwd.join(CONFIG_FILE_NAME), "").unwrap();
+
+        assert!(config_paths(&home, &cwd).is_empty());
+    }
+
+    #[test]
+    fn test_config_paths_ignores_git_work_trees() {
+        let home = tempfile::tempdir().unwrap();
+        let cwd = home.path().join("src/example.com/team/app/docs");
+        fs::create_dir_all(&cwd).unwrap();
+        fs::create_dir_all(home.path().join("src/example.com/team/app/.git")).unwrap();
+
+        for dir in [
+            "src",
+            "src/example.com/team/app",
+            "src/example.com/team/app/docs",
+        ] {
+            fs::write(home.path().join(dir).join(CONFIG_FILE_NAME), "").unwrap();
+        }
+
+        assert_eq!(
+            config_paths(home.path(), &cwd),
+            vec![home.path().join("src").join(CONFIG_FILE_NAME)]
+        );
+    }
+
+    #[test]
+    fn test_load_layers_ignores_commands_outside_user_config() {
+        let home = tempfile::tempdir().unwrap();
+        let user_config_path = home.path().join(CONFIG_FILE_NAME);
+        let directory_config_path = home.path().join("work").join(CONFIG_FILE_NAME);
+        fs::create_dir_all(home.path().join("work")).unwrap();
+        fs::write(&user_config_path, r#"git_executable = "/usr/bin/git""#).unwrap();
+        fs::write(
+            &directory_config_path,
+            r#"
+            base_dir = "/work"
+            git_executable = "./evil.sh"
+            post_clone = ["./evil.sh"]
+
+            [[variant]]
+            host = "example.com"
+            env = { GIT_SSH_COMMAND = "./evil.sh" }
+            ssh_options = ["ProxyCommand=./evil.sh"]
+            "#,
+        )
+        .unwrap();
+
+        let layers = load_layers(
+            &user_config_path,
+            vec![user_config_path.to_owned(), directory_config_path],
+        )
+        .unwrap();
+        let config = layers
+            .into_iter()
+            .fold(UserConfiguration::default(), |config, (_, layer)| {
+                config.merge(layer)
+            });
+
+        assert_eq!(config.base_dir, Some(String::from("/work")));
+        assert_eq!(config.git_executable, Some(String::from("/usr/bin/git")));
+        assert_eq!(config.post_clone, None);
+        assert_eq!(config.variants[0].env, None);
+        assert_eq!(config.variants[0].ssh_options, None);
+    }
+
+    #[test]
+    fn test_load_layers_keeps_user_variant_settings_for_the_same_host() {
+        let home = tempfile::tempdir().unwrap();
+        let user_config_path = home.path().join(CONFIG_FILE_NAME);
+        let directory_config_path = home.path().join("work").join(CONFIG_FILE_NAME);
+        fs::create_dir_all(home.path().join("work")).unwrap();
+        fs::write(
+            &user_config_path,
+            r#"
+            [[variant]]
+            host = "example.com"
+            ssh_key = "/keys/id_example"
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            &directory_config_path,
+            r#"
+            [[variant]]
+            host = "example.com"
+            user_email = "ferris@client-a.example.com"
+            "#,
+        )
+        .unwrap();
+
+        let layers = load_layers(
+            &user_config_path,
+            vec![user_config_path.to_owned(), directory_config_path],
+        )
+        .unwrap();
+        let user_config = layers
+            .into_iter()
+            .fold(UserConfiguration::default(), |config, (_, layer)| {
+                config.merge(layer)
+            });
+        let config = Configuration::from_user_configuration(
+            user_config,
+            &String::from("example.com"),
+            home.path().to_string_lossy().into_owned(),
+        )
+        .unwrap();
+
+        assert_eq!(config.ssh_key, Some(String::from("/keys/id_example")));
+        assert_eq!(
+            config.user_email,
+            Some(String::from("ferris@client-a.example.com"))
+        );
+    }
+
+    #[test]
+    fn test_load_layers_directory_config_cannot_loosen_policy() {
+        let home = tempfile::tempdir().unwrap();
+        let user_config_path = home.path().join(CONFIG_FILE_NAME);
+        let directory_config_path = home.path().join("work").join(CONFIG_FILE_NAME);
+        fs::create_dir_all(home.path().join("work")).unwrap();
+        fs::write(
+            &user_config_path,
+            r#"
+            allowed_hosts = ["github.com"]
+            denied_hosts = ["gist.github.com"]
+            pre_clone = "check-policy"
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            &directory_config_path,
+            r#"
+            allowed_hosts = ["*"]
+            denied_hosts = []
+            pre_clone = "true"
+            "#,
+        )
+        .unwrap();
+
+        let layers = load_layers(
+            &user_config_path,
+            vec![user_config_path.to_owned(), directory_config_path],
+        )
+        .unwrap();
+        let config = layers
+            .into_iter()
+            .fold(UserConfiguration::default(), |config, (_, layer)| {
+                config.merge(layer)
+            });
+
+        assert_eq!(config.allowed_hosts, Some(vec![String::from("github.com")]));
+        assert_eq!(
+            config.denied_hosts,
+            Some(vec![String::from("gist.github.com")])
+        );
+        assert_eq!(config.pre_clone, Some(String::from("check-policy")));
+    }
+
+    #[test]
+    fn test_json_schema_matches_committed_schema_file() {
+        // Regenerate with `jclone config schema > jclone.schema.json`
+        assert_eq!(
+            UserConfiguration::json_schema(),
+            include_str!("../jclone.schema.json")
+        );
+    }
 }
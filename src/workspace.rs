@@ -0,0 +1,435 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::os::unix;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use serde::Serialize;
+
+use crate::layout::{self, Layout};
+use crate::pattern::{glob_match, host_matches};
+
+/// A git checkout found under one of the base directories.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Checkout {
+    pub dir: PathBuf,
+    /// `None` when the layout doesn't include the host, e.g. with `use_host_dir = false`.
+    pub host: Option<String>,
+    pub path: String,
+}
+
+impl Checkout {
+    /// The host and path, e.g. `github.com/rust-lang/rustlings`, or just the path if the host
+    /// isn't known.
+    pub fn name(&self) -> String {
+        match &self.host {
+            Some(host) => format!("{host}/{}", self.path),
+            None => self.path.to_owned(),
+        }
+    }
+}
+
+/// Which checkouts a command applies to.
+#[derive(Debug, PartialEq, Default)]
+pub struct Filter {
+    /// Host pattern, e.g. `*.example.com`.
+    pub host: Option<String>,
+    /// Path pattern, e.g. `rust-lang/*`.
+    pub path: Option<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, checkout: &Checkout) -> bool {
+        let host_matches = match (&self.host, &checkout.host) {
+            (None, _) => true,
+            (Some(pattern), Some(host)) => host_matches(pattern, host),
+            (Some(_), None) => false,
+        };
+
+        host_matches
+            && self
+                .path
+                .as_ref()
+                .is_none_or(|pattern| glob_match(pattern, &checkout.path))
+    }
+}
+
+/// Collects the git checkouts under `dir`, without looking inside checkouts, hidden directories,
+/// symlinks or any of the directories in `skip`. Symlinks to checkouts, like the ones
+/// `jclone adopt --symlink` makes, are collected. Directories that can't be read are skipped.
+fn find_git_dirs(dir: &Path, skip: &[&Path], found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+
+        if !(file_type.is_dir() || file_type.is_symlink())
+            || hidden
+            || skip.contains(&path.as_path())
+        {
+            continue;
+        }
+
+        match path.join(".git").exists() {
+            true => found.push(path),
+            false if file_type.is_dir() => find_git_dirs(&path, skip, found),
+            false => (),
+        }
+    }
+}
+
+/// Finds the git checkouts under `dir`, including `dir` itself if it's one.
+pub fn find_checkout_dirs(dir: &Path) -> Vec<PathBuf> {
+    if dir.join(".git").exists() {
+        return vec![dir.to_owned()];
+    }
+
+    let mut dirs = vec![];
+    find_git_dirs(dir, &[], &mut dirs);
+    dirs.sort();
+    dirs
+}
+
+/// Finds every git checkout under the base directories of `layouts`, mapped back to the
+/// repository it was cloned from. Base directories inside other base directories are only
+/// searched once, using their own layouts.
+pub fn find_checkouts(layouts: &[Layout]) -> Vec<Checkout> {
+    let base_dirs: BTreeSet<&Path> = layouts
+        .iter()
+        .map(|layout| layout.base_dir.as_path())
+        .collect();
+
+    let mut checkouts = vec![];
+
+    for base_dir in &base_dirs {
+        let nested: Vec<&Path> = base_dirs
+            .iter()
+            .filter(|dir| *dir != base_dir && dir.starts_with(base_dir))
+            .copied()
+            .collect();
+
+        let base_layouts: Vec<&Layout> = layouts
+            .iter()
+            .filter(|layout| layout.base_dir == *base_dir)
+            .collect();
+
+        let mut dirs = vec![];
+        find_git_dirs(base_dir, &nested, &mut dirs);
+
+        for dir in dirs {
+            let relative = dir.strip_prefix(base_dir).unwrap_or(&dir);
+            let (host, path) = layout::repository_for(&base_layouts, relative);
+
+            checkouts.push(Checkout { dir, host, path });
+        }
+    }
+
+    checkouts.sort_by(|a, b| a.dir.cmp(&b.dir));
+    checkouts
+}
+
+/// Copies the directory `from` to `to`, keeping symlinks as symlinks.
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir(to)?;
+    fs::set_permissions(to, fs::metadata(from)?.permissions())?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+
+        if file_type.is_symlink() {
+            unix::fs::symlink(fs::read_link(entry.path())?, &target)?;
+        } else if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the directory `from` to `to`, creating `to`'s parents. Moves to another filesystem
+/// copy everything before removing `from`, so nothing is lost if the copy fails part way.
+pub fn move_dir(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match fs::rename(from, to) {
+        Err(err) if err.kind() == ErrorKind::CrossesDevices => {
+            if let Err(err) = copy_dir(from, to) {
+                let _ = fs::remove_dir_all(to);
+                return Err(err);
+            }
+
+            fs::remove_dir_all(from)
+        }
+        result => result,
+    }
+}
+
+/// Removes the parents of `dir` that are now empty, stopping at the closest of `stop_dirs`
+/// it's inside. Nothing is removed if it isn't inside any of them.
+pub fn remove_empty_parents(dir: &Path, stop_dirs: &[&Path]) {
+    let Some(stop_dir) = stop_dirs
+        .iter()
+        .filter(|stop_dir| dir.starts_with(stop_dir))
+        .max_by_key(|stop_dir| stop_dir.components().count())
+    else {
+        return;
+    };
+
+    // Removing a directory that isn't empty fails, which is where this stops
+    for parent in dir.ancestors().skip(1) {
+        if parent == *stop_dir || fs::remove_dir(parent).is_err() {
+            break;
+        }
+    }
+}
+
+/// How many checkouts to work on at once by default: one per CPU.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(4, |jobs| jobs.get())
+}
+
+/// Calls `f` on every item using up to `jobs` threads, returning the results in the same order
+/// as `items`.
+pub fn map_parallel<'a, T: Sync, R: Send>(
+    items: &'a [T],
+    jobs: usize,
+    f: impl Fn(&'a T) -> R + Sync,
+) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+
+                    let result = f(item);
+                    results.lock().unwrap_or_else(|err| err.into_inner())[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_else(|err| err.into_inner())
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn checkout(host: Option<&str>, path: &str) -> Checkout {
+        Checkout {
+            dir: PathBuf::from("/src").join(path),
+            host: host.map(String::from),
+            path: String::from(path),
+        }
+    }
+
+    #[rstest]
+    #[case::no_filter(Filter::default(), Some("github.com"), true)]
+    #[case::host(Filter { host: Some(String::from("*.example.com")), path: None }, Some("git.example.com"), true)]
+    #[case::other_host(Filter { host: Some(String::from("github.com")), path: None }, Some("git.example.com"), false)]
+    #[case::unknown_host(Filter { host: Some(String::from("*")), path: None }, None, false)]
+    #[case::path(Filter { host: None, path: Some(String::from("rust-lang/*")) }, None, true)]
+    #[case::other_path(Filter { host: None, path: Some(String::from("acme/*")) }, None, false)]
+    fn test_filter_matches(
+        #[case] filter: Filter,
+        #[case] host: Option<&str>,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(
+            filter.matches(&checkout(host, "rust-lang/rustlings")),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::host(Some("github.com"), "github.com/rust-lang/rustlings")]
+    #[case::unknown_host(None, "rust-lang/rustlings")]
+    fn test_checkout_name(#[case] host: Option<&str>, #[case] expected: &str) {
+        assert_eq!(checkout(host, "rust-lang/rustlings").name(), expected);
+    }
+
+    #[rstest]
+    #[case::one_job(1)]
+    #[case::more_jobs_than_items(64)]
+    fn test_map_parallel_keeps_order(#[case] jobs: usize) {
+        let items: Vec<u64> = (0..20).collect();
+
+        assert_eq!(
+            map_parallel(&items, jobs, |n| n * 2),
+            (0..20).map(|n| n * 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_find_checkouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        let work = src.join("work");
+
+        for checkout in [
+            "github.com/rust-lang/rustlings/.git",
+            "github.com/rust-lang/rustlings/nested/.git",
+            "github.com/.hidden/repo/.git",
+            "gitlab.com/group/sub/proj/.git",
+            "gitlab.com/group/not-a-checkout",
+            "work/team/app/.git",
+        ] {
+            fs::create_dir_all(src.join(checkout)).unwrap();
+        }
+
+        // Adopted with --symlink, and a symlink to a directory that isn't a checkout
+        let elsewhere = dir.path().join("elsewhere");
+        fs::create_dir_all(elsewhere.join("linked/.git")).unwrap();
+        fs::create_dir_all(elsewhere.join("other/repo/.git")).unwrap();
+        unix::fs::symlink(
+            elsewhere.join("linked"),
+            src.join("gitlab.com/group/linked"),
+        )
+        .unwrap();
+        unix::fs::symlink(elsewhere.join("other"), src.join("gitlab.com/other")).unwrap();
+
+        let layouts = vec![
+            Layout {
+                base_dir: src.to_owned(),
+                host: None,
+                use_host_dir: true,
+            },
+            Layout {
+                base_dir: work.to_owned(),
+                host: Some(String::from("git.example.com")),
+                use_host_dir: false,
+            },
+        ];
+
+        let found = find_checkouts(&layouts);
+
+        assert_eq!(
+            found,
+            vec![
+                Checkout {
+                    dir: src.join("github.com/rust-lang/rustlings"),
+                    host: Some(String::from("github.com")),
+                    path: String::from("rust-lang/rustlings"),
+                },
+                Checkout {
+                    dir: src.join("gitlab.com/group/linked"),
+                    host: Some(String::from("gitlab.com")),
+                    path: String::from("group/linked"),
+                },
+                Checkout {
+                    dir: src.join("gitlab.com/group/sub/proj"),
+                    host: Some(String::from("gitlab.com")),
+                    path: String::from("group/sub/proj"),
+                },
+                Checkout {
+                    dir: work.join("team/app"),
+                    host: Some(String::from("git.example.com")),
+                    path: String::from("team/app"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_checkout_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for checkout in ["team/app/.git", "team/api/.git", "notes"] {
+            fs::create_dir_all(dir.path().join(checkout)).unwrap();
+        }
+
+        assert_eq!(
+            find_checkout_dirs(dir.path()),
+            vec![dir.path().join("team/api"), dir.path().join("team/app")]
+        );
+        assert_eq!(
+            find_checkout_dirs(&dir.path().join("team/app")),
+            vec![dir.path().join("team/app")]
+        );
+    }
+
+    #[test]
+    fn test_copy_dir_keeps_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from");
+        fs::create_dir_all(from.join(".git/refs")).unwrap();
+        fs::write(from.join("README.md"), "hello").unwrap();
+        unix::fs::symlink("README.md", from.join("link")).unwrap();
+
+        copy_dir(&from, &dir.path().join("to")).unwrap();
+
+        let to = dir.path().join("to");
+        assert!(to.join(".git/refs").is_dir());
+        assert_eq!(fs::read_to_string(to.join("README.md")).unwrap(), "hello");
+        assert_eq!(
+            fs::read_link(to.join("link")).unwrap(),
+            PathBuf::from("README.md")
+        );
+    }
+
+    #[test]
+    fn test_move_dir_creates_parents() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("old/team/app");
+        let to = dir.path().join("new/example.com/team/app");
+        fs::create_dir_all(from.join(".git")).unwrap();
+
+        move_dir(&from, &to).unwrap();
+
+        assert!(!from.exists());
+        assert!(to.join(".git").is_dir());
+    }
+
+    #[test]
+    fn test_remove_empty_parents() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("example.com/team/app")).unwrap();
+        fs::create_dir_all(src.join("example.com/other")).unwrap();
+
+        remove_empty_parents(&src.join("example.com/team/app/gone"), &[dir.path(), &src]);
+
+        assert!(!src.join("example.com/team/app").exists());
+        assert!(!src.join("example.com/team").exists());
+        assert!(src.join("example.com/other").exists());
+    }
+
+    #[test]
+    fn test_remove_empty_parents_outside_stop_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = dir.path().join("elsewhere/empty");
+        fs::create_dir_all(&empty).unwrap();
+
+        remove_empty_parents(&empty.join("app"), &[&dir.path().join("src")]);
+
+        assert!(empty.exists());
+    }
+}